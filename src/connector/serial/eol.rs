@@ -143,10 +143,7 @@ impl AsciiCmdRespProtocol for Driver {
 
         //
         // Build response string
-        unsafe {
-            let string_slice =
-                String::from_utf8_unchecked(self.read_buffer[..count - self.eol.len()].to_vec());
-            return Ok(string_slice.to_string());
-        }
+        let response = &self.read_buffer[..count - self.eol.len()];
+        Ok(String::from_utf8_lossy(response).to_string())
     }
 }
@@ -0,0 +1,186 @@
+use super::eol::Driver as EolDriver;
+use super::settings::SERIAL_PORT_NAME_KEY;
+use super::Settings as SerialSettings;
+use crate::connector::usb::scanner::{instance_name, UsbScanner};
+use crate::protocol::AsciiCmdRespProtocol;
+use crate::{Error, ProductionOrder, ScanReporter, ScanResult, Scanner};
+use async_trait::async_trait;
+use futures::stream::{FuturesUnordered, StreamExt};
+use regex::Regex;
+use std::time::Duration;
+use tokio_serial::{available_ports as available_serial_ports, SerialPortType};
+
+/// Scanner that opens serial ports, sends an identification query
+/// and matches the response with a regex (ex: "*IDN?")
+///
+pub struct SerialScanner {
+    /// Name of the scanner
+    ///
+    name: String,
+
+    /// Driver reference to produce when the response matches
+    ///
+    dref: String,
+
+    /// Serial settings used to open candidate ports (port name is ignored)
+    ///
+    settings: SerialSettings,
+
+    /// End of line of the query and the response
+    ///
+    eol: Vec<u8>,
+
+    /// Identification query
+    ///
+    query: String,
+
+    /// Expected response
+    ///
+    response: Regex,
+
+    /// Only probe usb ports with those ids
+    ///
+    usb_ids: Option<(u16, u16)>,
+
+    /// Time given to the whole scan
+    ///
+    timeout: Duration,
+}
+
+impl SerialScanner {
+    /// Create a scanner, fails if 'response' is not a valid regex
+    ///
+    pub fn new<N: Into<String>, D: Into<String>, Q: Into<String>>(
+        name: N,
+        dref: D,
+        query: Q,
+        response: &str,
+    ) -> Result<Self, Error> {
+        let response = Regex::new(response)
+            .map_err(|e| Error::InvalidArgument(format!("Invalid response regex {:?}", e)))?;
+        Ok(Self {
+            name: name.into(),
+            dref: dref.into(),
+            settings: SerialSettings::new(),
+            eol: b"\n".to_vec(),
+            query: query.into(),
+            response,
+            usb_ids: None,
+            timeout: Duration::from_secs(10),
+        })
+    }
+
+    /// Set the settings used to open the ports
+    ///
+    pub fn with_settings(mut self, settings: SerialSettings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Set the end of line
+    ///
+    pub fn with_eol(mut self, eol: Vec<u8>) -> Self {
+        self.eol = eol;
+        self
+    }
+
+    /// Only probe the usb ports with those ids
+    ///
+    pub fn with_usb_ids(mut self, vid: u16, pid: u16) -> Self {
+        self.usb_ids = Some((vid, pid));
+        self
+    }
+
+    /// Set the time given to the whole scan
+    ///
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Send the query on the port and check the response
+    ///
+    async fn probe(&self, port_name: &str) -> Result<bool, Error> {
+        let settings = self.settings.clone().set_port_name(port_name);
+        let mut driver = EolDriver::open(&settings, self.eol.clone())?;
+        let response = driver.ask(&self.query).await?;
+        Ok(self.response.is_match(response.trim()))
+    }
+}
+
+#[async_trait]
+impl Scanner for SerialScanner {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    async fn scan(&self, reporter: ScanReporter) -> Vec<ScanResult> {
+        let ports = match available_serial_ports() {
+            Ok(ports) => ports,
+            Err(e) => {
+                reporter.finished(format!("cannot list serial ports: {:?}", e));
+                return Vec::new();
+            }
+        };
+
+        //
+        // Select candidate ports
+        let candidates: Vec<_> = ports
+            .into_iter()
+            .filter(|port| match (self.usb_ids, &port.port_type) {
+                (None, _) => true,
+                (Some((vid, pid)), SerialPortType::UsbPort(info)) => {
+                    info.vid == vid && info.pid == pid
+                }
+                (Some(_), _) => false,
+            })
+            .collect();
+
+        //
+        // Probe them in parallel, each probe is limited by the read timeout of the settings
+        // and the progress is reported as soon as a probe ends
+        let total = candidates.len() as u32;
+        let mut probes: FuturesUnordered<_> = candidates
+            .iter()
+            .enumerate()
+            .map(|(index, port)| async move { (index, self.probe(&port.port_name).await) })
+            .collect();
+
+        let mut matching = Vec::new();
+        let mut done = 0;
+        while let Some((index, answer)) = probes.next().await {
+            done += 1;
+            reporter.progress(done, total, candidates[index].port_name.clone());
+            if matches!(answer, Ok(true)) {
+                matching.push(index);
+            }
+        }
+        matching.sort();
+
+        let mut results = Vec::new();
+        for index in matching {
+            let port = &candidates[index];
+            let order = match &port.port_type {
+                SerialPortType::UsbPort(info) => UsbScanner::production_order(
+                    &self.dref,
+                    info.vid,
+                    info.pid,
+                    info.serial_number.as_deref(),
+                    index,
+                ),
+                _ => ProductionOrder::new(
+                    self.dref.as_str(),
+                    instance_name(&self.dref, &port.port_name),
+                ),
+            }
+            .add_string_setting(SERIAL_PORT_NAME_KEY, port.port_name.clone());
+
+            results.push(ScanResult::new(order));
+        }
+        results
+    }
+}
@@ -174,9 +174,6 @@ impl AsciiCmdRespProtocol for Driver {
 
         //
         // Build response string
-        unsafe {
-            let string_slice = String::from_utf8_unchecked(self.read_buffer[..count].to_vec());
-            return Ok(string_slice.to_string());
-        }
+        Ok(String::from_utf8_lossy(&self.read_buffer[..count]).to_string())
     }
 }
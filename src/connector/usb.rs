@@ -1,5 +1,7 @@
+pub mod scanner;
 pub mod sdk_helper;
 pub mod settings;
 pub mod tmc;
 
+pub use scanner::UsbScanner;
 pub use settings::Settings;
@@ -0,0 +1,144 @@
+use crate::{
+    ProductionOrder, ScanReporter, ScanResult, Scanner, SETTINGS_USB_PID_KEY,
+    SETTINGS_USB_SERIAL_KEY, SETTINGS_USB_VID_KEY,
+};
+use async_trait::async_trait;
+
+/// Confidence of a result only based on the usb ids
+///
+static USB_IDS_CONFIDENCE: f32 = 0.8;
+
+/// Usb ids that identify a driver
+///
+#[derive(Debug, Clone)]
+pub struct UsbMatch {
+    /// Vendor ID
+    ///
+    pub vid: u16,
+
+    /// Product ID
+    ///
+    pub pid: u16,
+
+    /// Driver reference to produce when the ids match
+    ///
+    pub dref: String,
+}
+
+/// Scanner that finds usb devices from a table of (vid, pid, dref)
+///
+pub struct UsbScanner {
+    /// Name of the scanner
+    ///
+    name: String,
+
+    /// Ids to look for
+    ///
+    table: Vec<UsbMatch>,
+}
+
+impl UsbScanner {
+    /// Create a scanner without any entry
+    ///
+    pub fn new<N: Into<String>>(name: N) -> Self {
+        Self {
+            name: name.into(),
+            table: Vec::new(),
+        }
+    }
+
+    /// Produce 'dref' for each device with those ids
+    ///
+    pub fn add_match<D: Into<String>>(mut self, vid: u16, pid: u16, dref: D) -> Self {
+        self.table.push(UsbMatch {
+            vid,
+            pid,
+            dref: dref.into(),
+        });
+        self
+    }
+
+    /// Build the production order of a found device
+    ///
+    pub fn production_order(
+        dref: &str,
+        vid: u16,
+        pid: u16,
+        serial: Option<&str>,
+        index: usize,
+    ) -> ProductionOrder {
+        let id = serial.map(|s| s.to_string()).unwrap_or(index.to_string());
+        let order = ProductionOrder::new(dref, instance_name(dref, &id))
+            .add_u16_setting(SETTINGS_USB_VID_KEY!(), vid)
+            .add_u16_setting(SETTINGS_USB_PID_KEY!(), pid);
+        match serial {
+            Some(serial) => order.add_string_setting(SETTINGS_USB_SERIAL_KEY!(), serial),
+            None => order,
+        }
+    }
+}
+
+#[async_trait]
+impl Scanner for UsbScanner {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    async fn scan(&self, reporter: ScanReporter) -> Vec<ScanResult> {
+        let devices = match nusb::list_devices() {
+            Ok(devices) => devices,
+            Err(e) => {
+                reporter.finished(format!("cannot list usb devices: {}", e));
+                return Vec::new();
+            }
+        };
+
+        let mut results = Vec::new();
+        for dev in devices {
+            for entry in &self.table {
+                if dev.vendor_id() == entry.vid && dev.product_id() == entry.pid {
+                    let order = Self::production_order(
+                        &entry.dref,
+                        entry.vid,
+                        entry.pid,
+                        dev.serial_number(),
+                        results.len(),
+                    );
+                    results.push(ScanResult::new(order).with_confidence(USB_IDS_CONFIDENCE));
+                }
+            }
+        }
+        results
+    }
+}
+
+/// Instance name built from the driver reference and a device id
+///
+/// ex: ("panduza.fake_psu", "A1B2") => "fake_psu_A1B2"
+///
+pub fn instance_name(dref: &str, id: &str) -> String {
+    let base = dref.rsplit('.').next().unwrap_or(dref);
+    let id: String = id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    format!("{}_{}", base, id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{instance_name, UsbScanner};
+    use serde_json::json;
+
+    #[test]
+    fn test_production_order() {
+        assert_eq!(instance_name("panduza.fake_psu", "A1/B2"), "fake_psu_A1_B2");
+
+        let order = UsbScanner::production_order("panduza.psu", 0x16c0, 0x05e1, Some("ABC"), 0);
+        assert_eq!(order.name, "psu_ABC");
+        assert_eq!(
+            order.settings,
+            Some(json!({ "usb_vid": 0x16c0, "usb_pid": 0x05e1, "usb_serial": "ABC" }))
+        );
+    }
+}
@@ -211,3 +211,26 @@ impl std::fmt::Display for Settings {
         )
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Settings;
+    use crate::{Prop, PropType, Props};
+    use serde_json::json;
+
+    #[test]
+    fn test_settings_from_validated_defaults() {
+        let mut props = Props::default();
+        props.add_number_prop(SETTINGS_USB_VID_KEY!(), "usb vendor", 0x16c0);
+        props.add_prop(
+            SETTINGS_USB_PID_KEY!(),
+            Prop::new("usb model", PropType::Number, json!(1505.0)),
+        );
+
+        let mut settings = None;
+        assert!(props.validate_settings("dev", &mut settings).is_valid());
+        let usb = Settings::from_json_settings(&settings.unwrap());
+        assert_eq!(usb.vid, Some(0x16c0));
+        assert_eq!(usb.pid, Some(1505));
+    }
+}
@@ -24,6 +24,8 @@ pub enum Error {
     SerializeFailure(String),
     #[error("Error during deserialization")]
     DeserializeError(String),
+    #[error("Invalid device tree: {0}")]
+    TreeError(String),
     #[error("Error related to plugin management")]
     PluginError(String),
     #[error("Error managing a cross task channel")]
@@ -38,10 +40,42 @@ pub enum Error {
 
     #[error("Driver operation failure")]
     DriverError(String),
+    #[error("Driver operation failure that does not prevent the instance to run")]
+    DriverWarning(String),
+    #[error("Operation timed out")]
+    Timeout(String),
+    #[error("'{0}' is disabled")]
+    Disabled(String),
     #[error("We just don't know what happened")]
     Wtf,
 }
 
+impl Error {
+    /// True if the instance can keep running after this error
+    ///
+    /// A recoverable error returned by a task moves the instance into the Warning
+    /// state until the task completes a run or 'clear_warning' is called. Any other
+    /// error returned by a task moves the instance into the Error state.
+    ///
+    pub fn is_recoverable(&self) -> bool {
+        matches!(
+            self,
+            Error::PublishError { .. }
+                | Error::DeserializeError(_)
+                | Error::EnumOutOfChoices(_)
+                | Error::SiOutOfRange(_)
+                | Error::DriverWarning(_)
+                | Error::Timeout(_)
+        )
+    }
+
+    /// True if the instance cannot keep running after this error
+    ///
+    pub fn is_fatal(&self) -> bool {
+        !self.is_recoverable()
+    }
+}
+
 #[macro_export]
 macro_rules! format_settings_error {
     ($($arg:tt)*) => {
@@ -55,3 +89,10 @@ macro_rules! format_driver_error {
         Error::DriverError(format!($($arg)*))
     };
 }
+
+#[macro_export]
+macro_rules! format_driver_warning {
+    ($($arg:tt)*) => {
+        Error::DriverWarning(format!($($arg)*))
+    };
+}
@@ -1,12 +1,14 @@
 pub mod production_order;
+pub mod scan_result;
 pub mod store;
 use store::{Product, Store};
-use tokio::sync::mpsc::Sender;
 
+use crate::props::validation::{ValidationIssueKind, ValidationReport};
 use crate::{
-    FactoryLogger, Instance, InstanceMonitor, Notification, Producer, ProductionOrder, Reactor,
-    Scanner,
+    Error, FactoryLogger, Instance, InstanceMonitor, NotificationGroup, NotificationSender,
+    Producer, ProductionOrder, Reactor, ScanReporter, ScanResult, Scanner,
 };
+use std::sync::{Arc, Mutex};
 use std::{collections::HashMap, ffi::CString};
 
 /// Factory to create devices from a configuration json
@@ -101,36 +103,101 @@ impl Factory {
             .map_err(|e| crate::Error::InternalLogic(format!("Failed to build CString ({:?})", e)))
     }
 
+    ///
+    /// Check if the factory can produce the given driver reference
+    ///
+    pub fn contains(&self, dref: &String) -> bool {
+        self.producers.contains_key(dref)
+    }
+
+    ///
+    /// Check the production order settings against the producer props
+    ///
+    /// Missing settings are filled with the default values of the props
+    ///
+    pub fn validate(&self, production_order: &mut ProductionOrder) -> ValidationReport {
+        match self.producers.get(production_order.dref()) {
+            Some(producer) => producer
+                .props()
+                .validate_settings(&production_order.name, &mut production_order.settings),
+            None => {
+                let mut report = ValidationReport::default();
+                report.push(
+                    production_order.name.clone(),
+                    None,
+                    ValidationIssueKind::UnknownDriverRef,
+                    format!("unknown driver reference '{}'", production_order.dref()),
+                );
+                report
+            }
+        }
+    }
+
+    ///
+    /// Check all the production orders at once, to report every problem to the user
+    ///
+    pub fn validate_all(&self, production_orders: &mut [ProductionOrder]) -> ValidationReport {
+        let mut report = ValidationReport::default();
+        for production_order in production_orders.iter_mut() {
+            report.extend(self.validate(production_order));
+        }
+        report
+    }
+
     ///
     /// production_order => json with ref, name, settings
     ///
+    /// Fail if the driver reference is unknown, if settings are not valid
+    /// or if the producer fails
+    ///
     pub fn produce(
         &self,
         reactor: Reactor,
-        r_notifier: Option<Sender<Notification>>,
-        production_order: ProductionOrder,
-    ) -> (InstanceMonitor, Instance) {
-        let producer = self.producers.get(production_order.dref()).unwrap();
-        let device_operations = producer.produce().unwrap();
+        r_notifier: Option<NotificationSender>,
+        mut production_order: ProductionOrder,
+    ) -> Result<(InstanceMonitor, Instance), Error> {
+        //
+        // Check settings before going further
+        let report = self.validate(&mut production_order);
+        if !report.is_valid() {
+            return Err(Error::BadSettings(report.to_string()));
+        }
+        for issue in &report.issues {
+            self.logger.warn(issue.to_string());
+        }
+
+        let producer = self.producers.get(production_order.dref()).ok_or_else(|| {
+            Error::BadSettings(format!(
+                "Unknown driver reference '{}' for instance '{}'",
+                production_order.dref(),
+                production_order.name
+            ))
+        })?;
+        let device_operations = producer.produce()?;
 
         // Box<dyn DriverOperations>
 
-        InstanceMonitor::new(
+        Ok(InstanceMonitor::new(
             reactor.clone(),
             r_notifier,
             device_operations,
             production_order,
-        )
+        ))
     }
 }
 
+/// Run the scanners to find the instances that can be produced on this server
 ///
-///
+#[derive(Clone)]
 pub struct ScanMachine {
     /// Local logger
     logger: FactoryLogger,
     ///
-    scanners: Vec<Box<dyn Scanner>>,
+    /// Scanners run by 'scan'
+    scanners: Vec<Arc<dyn Scanner>>,
+    ///
+    /// Where scanners report their progress
+    notifications: Option<Arc<Mutex<NotificationGroup>>>,
 }
 impl ScanMachine {
     /// Create a new factory
@@ -140,6 +207,7 @@ impl ScanMachine {
         let obj = Self {
             logger: FactoryLogger::new(),
             scanners: Vec::new(),
+            notifications: None,
         };
         // Info log
         obj.logger.info("# Scan Machine initialization");
@@ -158,25 +226,85 @@ impl ScanMachine {
         self.logger
             .info(format!("   - scanner - {}", scanner.name()));
 
-        self.scanners.push(scanner);
+        self.scanners.push(Arc::from(scanner));
     }
 
+    /// Scanners will report their progress into this group
     ///
+    pub fn set_notifications(&mut self, notifications: Arc<Mutex<NotificationGroup>>) {
+        self.notifications = Some(notifications);
+    }
+
     ///
+    /// Run all the scanners in parallel, each one is limited by its own timeout
     ///
-    pub fn scan(&self) -> Vec<ProductionOrder> {
-        let mut result = Vec::new();
+    /// Results that refer to the same physical device are deduplicated.
+    ///
+    pub async fn scan(&self) -> Vec<ScanResult> {
+        let mut handles = Vec::new();
         for scanner in &self.scanners {
-            result.extend(scanner.scan());
+            let scanner = scanner.clone();
+            let reporter = ScanReporter::new(scanner.name(), self.notifications.clone());
+            let logger = self.logger.clone();
+            handles.push(tokio::spawn(async move {
+                match tokio::time::timeout(scanner.timeout(), scanner.scan(reporter.clone())).await
+                {
+                    Ok(results) => {
+                        reporter.finished(format!("{} instance(s) found", results.len()));
+                        results
+                    }
+                    Err(_) => {
+                        logger.warn(format!("scanner '{}' timed out", scanner.name()));
+                        reporter.finished("timeout");
+                        Vec::new()
+                    }
+                }
+            }));
+        }
+
+        let mut result = Vec::new();
+        for handle in handles {
+            match handle.await {
+                Ok(found) => result.extend(found),
+                Err(e) => self.logger.warn(format!("scanner crashed: {:?}", e)),
+            }
         }
-        result
+        scan_result::deduplicate(result)
     }
 
     ///
+    /// Scan and wait for the results without async context (plugin C interface)
+    ///
+    /// The scan runs on a dedicated thread with its own runtime, so it can be called
+    /// from any thread, even one that already runs a runtime. When the scan is over
+    /// the runtime is shut down in background: the blocking probes of a scanner that
+    /// timed out are detached, not awaited.
+    ///
+    pub fn scan_blocking(&self) -> Result<Vec<ScanResult>, crate::Error> {
+        let machine = self.clone();
+        std::thread::Builder::new()
+            .name("pza-scan".to_string())
+            .spawn(move || {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()
+                    .map_err(|e| {
+                        crate::Error::InternalLogic(format!("Failed to build runtime ({:?})", e))
+                    })?;
+                let result = runtime.block_on(machine.scan());
+                runtime.shutdown_background();
+                Ok(result)
+            })
+            .map_err(|e| crate::Error::Spawn(format!("Failed to start the scan thread ({:?})", e)))?
+            .join()
+            .map_err(|_| crate::Error::InternalLogic("scan thread panicked".to_string()))?
+    }
+
     ///
+    /// Scan then serialize the results in json (plugin C interface)
     ///
     pub fn scan_as_c_string(&self) -> Result<CString, crate::Error> {
-        let result = self.scan();
+        let result = self.scan_blocking()?;
         let json_str =
             serde_json::to_string(&result).expect("Failed to serialize scan result to JSON");
         CString::new(json_str)
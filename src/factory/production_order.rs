@@ -3,7 +3,7 @@ use std::ffi::{c_char, CStr, CString};
 use serde_json::json;
 pub type InstanceSettings = serde_json::Value;
 
-#[derive(Debug, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
 pub struct ProductionOrder {
     /// Name of the device to be produced
     pub name: String,
@@ -58,6 +58,24 @@ impl ProductionOrder {
         self
     }
 
+    /// Add a setting of any serializable type
+    ///
+    pub fn add_setting<A: Into<String>, V: serde::Serialize>(
+        mut self,
+        name: A,
+        setting: V,
+    ) -> Result<Self, crate::Error> {
+        let value = serde_json::to_value(setting)
+            .map_err(|e| crate::Error::BadSettings(format!("{:?}", e)))?;
+        if self.settings.is_none() {
+            self.settings = Some(json!({}));
+        }
+        if let Some(obj) = self.settings.as_mut().and_then(|s| s.as_object_mut()) {
+            obj.insert(name.into(), value);
+        }
+        Ok(self)
+    }
+
     /// From a json value
     ///
     // pub fn from_json(value: &serde_json::Value) -> ProductionOrder {
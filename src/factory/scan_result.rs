@@ -0,0 +1,234 @@
+use crate::{Notification, NotificationGroup, ProductionOrder, ScanNotification};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, Mutex};
+
+/// Instance found by a scanner
+///
+/// Serialized as a production order with an extra 'confidence' field,
+/// so consumers that only expect production orders keep working.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ScanResult {
+    /// Production order to create the found instance
+    ///
+    #[serde(flatten)]
+    pub order: ProductionOrder,
+
+    /// How sure the scanner is about the driver (0.0 => guess, 1.0 => identified)
+    ///
+    pub confidence: f32,
+}
+
+impl ScanResult {
+    /// Result with a full confidence
+    ///
+    pub fn new(order: ProductionOrder) -> Self {
+        Self {
+            order,
+            confidence: 1.0,
+        }
+    }
+
+    /// Set the confidence, clamped into [0.0, 1.0]
+    ///
+    pub fn with_confidence(mut self, confidence: f32) -> Self {
+        self.confidence = confidence.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Key that identifies the physical device behind the result
+    ///
+    /// The usb serial number is used first, then the serial port name.
+    ///
+    pub fn device_key(&self) -> Option<String> {
+        let settings = self.order.settings.as_ref()?;
+        if let Some(serial) = settings.get("usb_serial").and_then(|v| v.as_str()) {
+            return Some(format!("usb:{}", serial));
+        }
+        if let Some(port) = settings.get("serial_port_name").and_then(|v| v.as_str()) {
+            return Some(format!("port:{}", port));
+        }
+        None
+    }
+}
+
+impl From<ProductionOrder> for ScanResult {
+    fn from(order: ProductionOrder) -> Self {
+        ScanResult::new(order)
+    }
+}
+
+/// Keep only the most confident result for each physical device
+///
+/// Results without device key are all kept, the order of first appearance is kept.
+///
+pub fn deduplicate(results: Vec<ScanResult>) -> Vec<ScanResult> {
+    let mut kept: Vec<ScanResult> = Vec::new();
+    for result in results {
+        let key = result.device_key();
+        let existing = key
+            .as_ref()
+            .and_then(|k| kept.iter().position(|r| r.device_key().as_ref() == Some(k)));
+        match existing {
+            Some(index) => {
+                if result.confidence > kept[index].confidence {
+                    kept[index] = result;
+                }
+            }
+            None => kept.push(result),
+        }
+    }
+    kept
+}
+
+/// Given to scanners to report their progress as notifications
+///
+#[derive(Clone)]
+pub struct ScanReporter {
+    /// Name of the scanner
+    ///
+    scanner: String,
+
+    /// Where to push notifications, reports are dropped if 'None'
+    ///
+    notifications: Option<Arc<Mutex<NotificationGroup>>>,
+}
+
+impl ScanReporter {
+    /// Create a reporter for the given scanner
+    ///
+    pub fn new<N: Into<String>>(
+        scanner: N,
+        notifications: Option<Arc<Mutex<NotificationGroup>>>,
+    ) -> Self {
+        Self {
+            scanner: scanner.into(),
+            notifications,
+        }
+    }
+
+    /// Report that 'step' over 'total' steps are done
+    ///
+    pub fn progress<M: Into<String>>(&self, step: u32, total: u32, message: M) {
+        self.push(ScanNotification::new(
+            self.scanner.clone(),
+            step,
+            total,
+            message,
+        ));
+    }
+
+    /// Report the end of the scan
+    ///
+    pub fn finished<M: Into<String>>(&self, message: M) {
+        let mut notification = ScanNotification::new(self.scanner.clone(), 1, 1, message);
+        notification.finished = true;
+        self.push(notification);
+    }
+
+    fn push(&self, notification: ScanNotification) {
+        if let Some(notifications) = &self.notifications {
+            if let Ok(mut group) = notifications.lock() {
+                group.push(Notification::from(notification));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{deduplicate, ScanReporter, ScanResult};
+    use crate::{ProductionOrder, ScanMachine, Scanner};
+    use async_trait::async_trait;
+    use std::time::Duration;
+
+    struct FakeScanner {
+        delay: Duration,
+    }
+
+    #[async_trait]
+    impl Scanner for FakeScanner {
+        fn name(&self) -> String {
+            format!("fake-{}", self.delay.as_millis())
+        }
+
+        fn timeout(&self) -> Duration {
+            Duration::from_millis(200)
+        }
+
+        async fn scan(&self, _reporter: ScanReporter) -> Vec<ScanResult> {
+            tokio::time::sleep(self.delay).await;
+            vec![ProductionOrder::new("vendor.fake", self.name()).into()]
+        }
+    }
+
+    /// Scanner whose blocking probe never answers in time
+    ///
+    struct HungScanner;
+
+    #[async_trait]
+    impl Scanner for HungScanner {
+        fn name(&self) -> String {
+            "hung".to_string()
+        }
+
+        fn timeout(&self) -> Duration {
+            Duration::from_millis(200)
+        }
+
+        async fn scan(&self, _reporter: ScanReporter) -> Vec<ScanResult> {
+            let _ =
+                tokio::task::spawn_blocking(|| std::thread::sleep(Duration::from_secs(5))).await;
+            Vec::new()
+        }
+    }
+
+    #[test]
+    fn test_deduplicate() {
+        let usb =
+            ProductionOrder::new("vendor.psu", "psu").add_string_setting("usb_serial", "A1B2C3");
+        let serial = ProductionOrder::new("vendor.psu.serial", "psu_serial")
+            .add_string_setting("usb_serial", "A1B2C3");
+        let other = ProductionOrder::new("vendor.scope", "scope");
+
+        let results = deduplicate(vec![
+            ScanResult::new(usb).with_confidence(0.5),
+            ScanResult::new(other.clone()),
+            ScanResult::new(serial.clone()).with_confidence(0.9),
+        ]);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].order, serial);
+        assert_eq!(results[1].order, other);
+    }
+
+    #[tokio::test]
+    async fn test_scan_timeout() {
+        let mut machine = ScanMachine::new();
+        machine.add_scanner(Box::new(FakeScanner {
+            delay: Duration::from_millis(10),
+        }));
+        machine.add_scanner(Box::new(FakeScanner {
+            delay: Duration::from_secs(5),
+        }));
+
+        let results = machine.scan().await;
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].order.name, "fake-10");
+    }
+
+    #[tokio::test]
+    async fn test_scan_blocking_detaches_hung_probes() {
+        let mut machine = ScanMachine::new();
+        machine.add_scanner(Box::new(FakeScanner {
+            delay: Duration::from_millis(10),
+        }));
+        machine.add_scanner(Box::new(HungScanner));
+
+        //
+        // Called from a runtime, it must neither panic nor wait for the hung probe
+        let start = std::time::Instant::now();
+        let results = machine.scan_blocking().unwrap();
+        assert!(start.elapsed() < Duration::from_secs(2));
+        assert_eq!(results.len(), 1);
+    }
+}
@@ -6,28 +6,36 @@ pub mod class_builder;
 pub mod container;
 pub mod element;
 pub mod monitor;
+pub mod task_registry;
 
 pub use container::Container;
 
 use crate::{
-    reactor::Reactor, AttributeBuilder, DriverOperations, Error, InstanceSettings, Notification,
-    TaskResult, TaskSender,
+    reactor::Reactor, AttributeBuilder, BooleanAttServer, DriverOperations, Error,
+    InstanceSettings, NotificationSender, TaskResult, TaskSender, TypedSettings,
 };
-use crate::{Logger, StateNotification};
+use crate::{AlertNotification, AlertSeverity, Logger, Notification, StateNotification};
 use class_builder::ClassBuilder;
+use element::SubElements;
+use futures::future::BoxFuture;
 use futures::FutureExt;
 pub use inner::InstanceInner;
 use serde::{Deserialize, Serialize};
-use std::{fmt::Display, future::Future, sync::Arc};
+use std::{fmt::Display, future::Future, panic::AssertUnwindSafe, sync::Arc};
+use task_registry::{RestartPolicy, TaskRegistry};
 use tokio::sync::Mutex;
-use tokio::sync::{mpsc::Sender, Notify};
+use tokio::sync::{watch, Notify};
 
-use crate::log_error;
+use crate::{log_error, log_warn};
 use async_trait::async_trait;
 
+/// Function that builds the future of a supervised task, called on each (re)start
+///
+type TaskFactory = Box<dyn FnMut() -> BoxFuture<'static, TaskResult> + Send>;
+
 /// States of the main Interface FSM
 ///
-#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum State {
     Booting,
     Connecting,
@@ -74,7 +82,7 @@ pub struct Instance {
     // ///
     // /// Device must share its status with the device "_" through this info object
     // info_dyn_dev_status: Option<Arc<Mutex<InfoDynamicDeviceStatus>>>,
-    pub r_notifier: Option<Sender<Notification>>,
+    pub r_notifier: Option<NotificationSender>,
 
     // started: bool,
     /// Inner object
@@ -91,6 +99,22 @@ pub struct Instance {
     // // logger: Logger,
     state: Arc<Mutex<State>>,
     state_change_notifier: Arc<Notify>,
+
+    /// Notify the instance monitor that subtasks must be cleaned
+    ///
+    clean_request_notifier: Arc<Notify>,
+
+    /// Health of the tasks spawned inside this instance
+    ///
+    tasks: Arc<Mutex<TaskRegistry>>,
+
+    /// Set to true once the instance is fully stopped
+    ///
+    stopped: Arc<watch::Sender<bool>>,
+
+    /// Classes and attributes created on the instance root
+    ///
+    sub_elements: SubElements,
     //
     //
     spawner: TaskSender<Result<(), Error>>,
@@ -104,7 +128,7 @@ impl Instance {
     ///
     pub fn new(
         reactor: Reactor,
-        r_notifier: Option<Sender<Notification>>,
+        r_notifier: Option<NotificationSender>,
         spawner: TaskSender<Result<(), Error>>,
         name: String,
         operations: Box<dyn DriverOperations>,
@@ -122,6 +146,10 @@ impl Instance {
             topic: format!("{}/{}", reactor.root_topic(), name),
             state: Arc::new(Mutex::new(State::Booting)),
             state_change_notifier: Arc::new(Notify::new()),
+            clean_request_notifier: Arc::new(Notify::new()),
+            tasks: Arc::new(Mutex::new(TaskRegistry::default())),
+            stopped: Arc::new(watch::channel(false).0),
+            sub_elements: SubElements::default(),
             spawner: spawner,
         }
     }
@@ -150,16 +178,26 @@ impl Instance {
     /// Run the FSM of the device
     ///
     pub async fn run_fsm(&mut self) {
+        //
+        // Write only attribute '_/reboot', created during the boot
+        let mut att_reboot: Option<BooleanAttServer> = None;
+
         //
         // First start by booting the device to give him a connection with the info_pack
         // and allow the InfoDevice to send device information on MQTT
         self.move_to_state(State::Booting).await;
 
         //
-        // Start the main loop of the device
-        // TODO => Maybe we should give a way to stop properly this task instead of canceling the task brutally
+        // Start the main loop of the device, it ends in the Stopping state
         loop {
-            self.state_change_notifier.notified().await;
+            tokio::select! {
+                _ = self.state_change_notifier.notified() => {}
+                _ = Self::wait_reboot_command(att_reboot.clone()) => {
+                    self.logger.info("reboot requested");
+                    self.move_to_state(State::Cleaning).await;
+                    continue;
+                }
+            }
 
             // Helper log
             let stateee = self.state.lock().await.clone();
@@ -175,10 +213,20 @@ impl Instance {
                     // } else {
                     //     self.logger.debug("FSM NO INFO PACK !");
                     // }
+                    match self.mount_underscore_class().await {
+                        Ok(att) => att_reboot = Some(att),
+                        Err(e) => {
+                            log_error!(self.logger, "Fail to mount instance '_' class '{:?}'", e);
+                        }
+                    }
                     self.move_to_state(State::Initializating).await;
                 }
                 State::Connecting => {} // wait for reactor signal
                 State::Initializating => {
+                    //
+                    // Elements of a previous mount are recreated by the driver
+                    self.forget_driver_elements().await;
+
                     //
                     // Try to mount the device
                     let mount_result = self.inner_operations.lock().await.mount(self.clone()).await;
@@ -191,30 +239,282 @@ impl Instance {
                         }
                         Err(e) => {
                             log_error!(self.logger, "Instance Mount Failure '{:?}'", e);
-                            self.move_to_state(State::Error).await;
+                            self.go_error_with_message(format!("{:?}", e)).await;
                         }
                     }
                 }
                 State::Running => {} // do nothing, watch for inner tasks
                 State::Error => {
                     //
-                    // Wait before reboot
-                    self.inner_operations
+                    // Wait before reboot, the user can also force it with '_/reboot'
+                    let operations = self.inner_operations.clone();
+                    let instance = self.clone();
+                    tokio::select! {
+                        _ = async move {
+                            operations.lock().await.wait_reboot_event(instance).await
+                        } => {}
+                        _ = Self::wait_reboot_command(att_reboot.clone()) => {}
+                        _ = self.state_change_notifier.notified() => {
+                            //
+                            // An other state has been requested (ex: Stopping)
+                            self.state_change_notifier.notify_one();
+                            continue;
+                        }
+                    }
+                    //
+                    // Clean the tasks left by the previous mount before mounting again
+                    self.logger.info("try to reboot");
+                    self.move_to_state(State::Cleaning).await;
+                }
+                State::Warning => {}
+                State::Cleaning => {
+                    //
+                    // The monitor aborts the subtasks then moves the instance to Initializating
+                    self.clean_request_notifier.notify_one();
+                }
+                State::Stopping => {
+                    //
+                    // Let the driver release its resources
+                    let unmount_result = self
+                        .inner_operations
                         .lock()
                         .await
-                        .wait_reboot_event(self.clone())
+                        .unmount(self.clone())
                         .await;
-                    self.logger.info("try to reboot");
-                    self.move_to_state(State::Initializating).await;
+                    if let Err(e) = unmount_result {
+                        log_warn!(self.logger, "Instance Unmount Failure '{:?}'", e);
+                    }
+                    //
+                    // The monitor aborts the subtasks then signals the end of the instance
+                    self.clean_request_notifier.notify_one();
+                    break;
                 }
-                State::Warning => {}
-                State::Cleaning => {}
-                State::Stopping => {}
                 State::Undefined => {}
             }
         }
 
-        // Ok(())
+        self.logger.debug("FSM stopped");
+    }
+
+    ///
+    /// Forget the elements mounted by the driver, only the '_' class is kept
+    ///
+    async fn forget_driver_elements(&self) {
+        let mut elements = self.sub_elements.lock().await;
+        let mut kept = Vec::new();
+        for element in elements.drain(..) {
+            if element.name().await == "_" {
+                kept.push(element);
+            }
+        }
+        *elements = kept;
+    }
+
+    ///
+    /// Mount the '_' class that exposes the instance status on the message broker
+    ///
+    /// Return the '_/reboot' attribute that the FSM must watch
+    ///
+    async fn mount_underscore_class(&mut self) -> Result<BooleanAttServer, Error> {
+        let mut class_underscore = self.create_class("_").finish().await;
+
+        let att_state = class_underscore
+            .create_attribute("state")
+            .with_ro()
+            .with_info("Current state of the instance")
+            .finish_as_string()
+            .await?;
+
+        let att_error = class_underscore
+            .create_attribute("error")
+            .with_ro()
+            .with_info("Last error that moved the instance into the Error state")
+            .finish_as_string()
+            .await?;
+
+        let att_reboot = class_underscore
+            .create_attribute("reboot")
+            .with_wo()
+            .with_info("Force the reinitialization of the instance")
+            .finish_as_boolean()
+            .await?;
+
+        let att_tasks = class_underscore
+            .create_attribute("tasks")
+            .with_ro()
+            .with_info("Health of the tasks spawned inside the instance")
+            .finish_as_json()
+            .await?;
+
+        {
+            let mut inner = self.inner.lock().await;
+            inner.att_state = Some(att_state);
+            inner.att_error = Some(att_error);
+            inner.att_tasks = Some(att_tasks);
+        }
+
+        //
+        // Publish the current status now that attributes exist
+        let state = self.state.lock().await.clone();
+        self.publish_status(&state).await;
+        self.publish_tasks().await;
+
+        Ok(att_reboot)
+    }
+
+    ///
+    /// Wait until a 'true' command is received on the '_/reboot' attribute
+    ///
+    /// Never returns if the attribute does not exist
+    ///
+    async fn wait_reboot_command(att_reboot: Option<BooleanAttServer>) {
+        match att_reboot {
+            Some(mut att) => loop {
+                att.wait_commands().await;
+                let mut requested = false;
+                while let Some(command) = att.pop_cmd().await {
+                    requested |= command;
+                }
+                if requested {
+                    return;
+                }
+            },
+            None => futures::future::pending().await,
+        }
+    }
+
+    ///
+    /// Publish state and last error on the '_' class attributes
+    ///
+    async fn publish_status(&self, state: &State) {
+        let (att_state, att_error, last_error) = {
+            let inner = self.inner.lock().await;
+            (
+                inner.att_state.clone(),
+                inner.att_error.clone(),
+                inner.last_error.clone(),
+            )
+        };
+
+        if let Some(att) = att_state {
+            if let Err(e) = att.set(state.to_string()).await {
+                log_warn!(self.logger, "Fail to publish instance state '{:?}'", e);
+            }
+        }
+        if let Some(att) = att_error {
+            if let Err(e) = att.set(last_error.unwrap_or_default()).await {
+                log_warn!(self.logger, "Fail to publish instance error '{:?}'", e);
+            }
+        }
+    }
+
+    ///
+    /// Publish the task registry on the '_/tasks' attribute
+    ///
+    async fn publish_tasks(&self) {
+        let att_tasks = self.inner.lock().await.att_tasks.clone();
+        if let Some(att) = att_tasks {
+            let value = self.tasks.lock().await.into_json_value();
+            if let Err(e) = att.set(value).await {
+                log_warn!(self.logger, "Fail to publish instance tasks '{:?}'", e);
+            }
+        }
+    }
+
+    ///
+    /// Registry of the tasks spawned inside this instance
+    ///
+    pub fn tasks(&self) -> Arc<Mutex<TaskRegistry>> {
+        self.tasks.clone()
+    }
+
+    ///
+    /// Mark all the tasks as aborted, or forget them if they will be mounted again
+    ///
+    pub(crate) async fn abort_tasks(&self, forget: bool) {
+        {
+            let mut tasks = self.tasks.lock().await;
+            if forget {
+                tasks.clear();
+                self.inner.lock().await.warning_reported = false;
+            } else {
+                tasks.abort_all();
+            }
+        }
+        self.publish_tasks().await;
+    }
+
+    ///
+    /// Register the task then request its execution to the instance monitor
+    ///
+    async fn spawn_supervised(
+        &mut self,
+        name: String,
+        policy: RestartPolicy,
+        factory: TaskFactory,
+    ) {
+        let id = self.tasks.lock().await.register(&name, policy.clone());
+        self.publish_tasks().await;
+
+        let instance = self.clone();
+        let future = instance.supervise(id, policy, factory);
+        if let Err(e) = self.spawner.spawn_with_name(name, future.boxed()) {
+            log_error!(self.logger, "Fail to spawn task '{:?}'", e);
+        }
+    }
+
+    ///
+    /// Run the task and restart it as long as its policy requires it
+    ///
+    async fn supervise(
+        mut self,
+        id: usize,
+        policy: RestartPolicy,
+        mut factory: TaskFactory,
+    ) -> TaskResult {
+        loop {
+            //
+            // A panic is considered as a task failure
+            let result = AssertUnwindSafe(factory())
+                .catch_unwind()
+                .await
+                .unwrap_or_else(|_| Err(Error::InternalLogic("task panicked".to_string())));
+
+            let delay = match policy.must_restart(&result) {
+                true => self.tasks.lock().await.next_restart_delay(id),
+                false => None,
+            };
+            self.tasks.lock().await.end(id, &result, delay.is_some());
+            self.publish_tasks().await;
+            let Some(delay) = delay else {
+                if result.is_ok() {
+                    self.clear_task_warning(id).await;
+                }
+                if policy.must_restart(&result) {
+                    log_error!(self.logger, "Task {} restarted too many times, give up", id);
+                }
+                return result;
+            };
+
+            log_warn!(
+                self.logger,
+                "Task {} ended ({:?}), restarting...",
+                id,
+                result
+            );
+            //
+            // A recoverable error keeps the instance in Warning until the task
+            // completes a run or the driver clears the warning
+            match result {
+                Err(e) if e.is_recoverable() => self.report_task_warning(id, e).await,
+                Ok(_) => self.clear_task_warning(id).await,
+                Err(_) => {}
+            }
+            tokio::time::sleep(delay).await;
+
+            self.tasks.lock().await.restart(id);
+            self.publish_tasks().await;
+        }
     }
 
     ///
@@ -224,6 +524,12 @@ impl Instance {
         self.inner.lock().await.settings.clone()
     }
 
+    /// Extract the settings of the device into the driver settings struct
+    ///
+    pub async fn typed_settings<T: TypedSettings>(&self) -> Result<T, Error> {
+        T::from_settings(&self.inner.lock().await.settings)
+    }
+
     pub fn name(&self) -> String {
         match self.topic.split('/').last() {
             Some(value) => value.to_string(),
@@ -236,26 +542,230 @@ impl Instance {
         self.move_to_state(State::Error).await;
     }
 
+    ///
+    /// Move to the Error state and keep the reason, it will be published on '_/error'
+    ///
+    pub async fn go_error_with_message<A: Into<String>>(&mut self, message: A) {
+        self.inner.lock().await.last_error = Some(message.into());
+        self.move_to_state(State::Error).await;
+    }
+
+    ///
+    /// Report a non-fatal error
+    ///
+    /// The error is logged and sent as an alert. If the instance is running,
+    /// it moves into the Warning state until 'clear_warning' is called.
+    ///
+    pub async fn report_warning(&mut self, error: Error) {
+        self.inner.lock().await.warning_reported = true;
+        self.enter_warning(error).await;
+    }
+
+    ///
+    /// The conditions reported with 'report_warning' or by the tasks are over, go back to Running
+    ///
+    pub async fn clear_warning(&mut self) {
+        self.inner.lock().await.warning_reported = false;
+        self.tasks.lock().await.clear_all_warnings();
+        self.publish_tasks().await;
+        self.leave_warning().await;
+    }
+
+    ///
+    /// Warning reported by the task 'id', only this task can clear it
+    ///
+    async fn report_task_warning(&mut self, id: usize, error: Error) {
+        self.tasks
+            .lock()
+            .await
+            .set_warning(id, format!("{:?}", error));
+        self.publish_tasks().await;
+        self.enter_warning(error).await;
+    }
+
+    ///
+    /// The task 'id' completed a run, its warning is over
+    ///
+    async fn clear_task_warning(&mut self, id: usize) {
+        if self.tasks.lock().await.clear_warning(id) {
+            self.publish_tasks().await;
+            self.leave_warning().await;
+        }
+    }
+
+    ///
+    /// Log and alert about the error, then move into Warning if the instance is running
+    ///
+    async fn enter_warning(&mut self, error: Error) {
+        log_warn!(self.logger, "Instance warning '{:?}'", error);
+        self.send_alert(format!("{:?}", error)).await;
+
+        let state = self.state.lock().await.clone();
+        if let State::Running | State::Warning = state {
+            self.inner.lock().await.last_error = Some(format!("{:?}", error));
+            self.move_to_state(State::Warning).await;
+        }
+    }
+
+    ///
+    /// Go back to Running once no warning is active anymore
+    ///
+    async fn leave_warning(&mut self) {
+        let active =
+            self.inner.lock().await.warning_reported || self.tasks.lock().await.has_warning();
+        let state = self.state.lock().await.clone();
+        if let (State::Warning, false) = (state, active) {
+            self.logger.info("warning cleared");
+            self.move_to_state(State::Running).await;
+        }
+    }
+
+    ///
+    /// Send an alert notification about this instance
+    ///
+    async fn send_alert(&self, message: String) {
+        let alert = AlertNotification::new(self.topic.clone(), message);
+        if let Err(e) = self.send_notification(alert.into()).await {
+            log_warn!(self.logger, "Fail to send alert notification '{:?}'", e);
+        }
+    }
+
+    ///
+    /// Send a notification to the runtime
+    ///
+    /// Instances without runtime (the '_' device) silently skip it.
+    ///
+    pub async fn send_notification(&self, notification: Notification) -> Result<(), Error> {
+        match &self.r_notifier {
+            Some(r_notifier) => r_notifier.send(notification).await,
+            None => Ok(()),
+        }
+    }
+
+    ///
+    /// Raise the condition 'code' (over-temperature...) on this instance
+    ///
+    /// The alert stays active, and published on '_/alerts', until 'clear_alert'.
+    ///
+    pub async fn raise_alert<C: Into<String>, M: Into<String>>(
+        &self,
+        severity: AlertSeverity,
+        code: C,
+        message: M,
+    ) -> Result<(), Error> {
+        let alert = AlertNotification::raised(self.topic.clone(), severity, code, message);
+        self.send_notification(alert.into()).await
+    }
+
+    ///
+    /// The condition 'code' raised on this instance is over
+    ///
+    pub async fn clear_alert<C: Into<String>>(&self, code: C) -> Result<(), Error> {
+        let alert = AlertNotification::cleared(self.topic.clone(), code);
+        self.send_notification(alert.into()).await
+    }
+
+    ///
+    /// Last error that moved the instance into the Error state
+    ///
+    pub async fn last_error(&self) -> Option<String> {
+        self.inner.lock().await.last_error.clone()
+    }
+
+    ///
+    /// Current state of the instance FSM
+    ///
+    pub async fn state(&self) -> State {
+        self.state.lock().await.clone()
+    }
+
+    ///
+    /// Replace the settings and remount the instance with them
+    ///
+    /// A running instance goes through Cleaning (subtasks aborted) then
+    /// Initializating, a booting instance just uses the new settings.
+    ///
+    pub async fn remount(&mut self, settings: Option<InstanceSettings>) {
+        self.inner.lock().await.settings = settings;
+        let state = self.state().await;
+        if let State::Running | State::Warning | State::Error = state {
+            self.logger.info("remount requested");
+            self.move_to_state(State::Cleaning).await;
+        }
+    }
+
+    ///
+    /// Request the instance to stop, see 'wait_stopped' to wait for the end
+    ///
+    pub async fn stop(&mut self) {
+        self.move_to_state(State::Stopping).await;
+    }
+
+    ///
+    /// Wait until the instance is fully stopped
+    ///
+    pub async fn wait_stopped(&self) {
+        let mut receiver = self.stopped.subscribe();
+        let _ = receiver.wait_for(|stopped| *stopped).await;
+    }
+
+    ///
+    /// True once the instance and all its subtasks are stopped
+    ///
+    pub fn is_stopped(&self) -> bool {
+        *self.stopped.borrow()
+    }
+
+    ///
+    /// Signal that the instance and all its subtasks are stopped
+    ///
+    pub(crate) fn notify_stopped(&self) {
+        self.stopped.send_replace(true);
+    }
+
+    ///
+    /// Notifier triggered when the FSM requests the cleaning of subtasks
+    ///
+    pub(crate) fn clean_request_notifier(&self) -> Arc<Notify> {
+        self.clean_request_notifier.clone()
+    }
+
     ///
     /// Function to change the current state of the device FSM
     ///
     pub async fn move_to_state(&mut self, new_state: State) {
-        // Set the new state
-        *self.state.lock().await = new_state.clone();
+        // Set the new state, nothing can restart a stopping instance
+        {
+            let mut state = self.state.lock().await;
+            if matches!(*state, State::Stopping) && !matches!(new_state, State::Stopping) {
+                return;
+            }
+            *state = new_state.clone();
+        }
 
         // println!("new state !!! {:?}", new_state.clone());
 
+        //
+        // The error is over once the instance runs again
+        if let State::Running = new_state {
+            self.inner.lock().await.last_error = None;
+        }
+
         // Alert monitoring device "_"
-        if let Some(r_notifier) = &mut self.r_notifier {
-            r_notifier
-                .try_send(StateNotification::new(self.topic.clone(), new_state.clone()).into())
-                .unwrap();
+        // States are coalesced by the sender, so this never waits
+        let notification = StateNotification::new(self.topic.clone(), new_state.clone());
+        if let Err(e) = self.send_notification(notification.into()).await {
+            log_warn!(self.logger, "Fail to send state notification '{:?}'", e);
         }
         // else {
         //     self.logger
         //         .debug("!!!!!!! DEBUG !!!!!!! r_notifier is 'None'");
         // }
 
+        //
+        // Also share it with message clients
+        self.publish_status(&new_state).await;
+
         // Notify FSM
         self.state_change_notifier.notify_one();
     }
@@ -269,6 +779,12 @@ impl Container for Instance {
         &self.logger
     }
 
+    /// Override
+    ///
+    fn sub_elements(&self) -> SubElements {
+        self.sub_elements.clone()
+    }
+
     /// Override
     ///
     fn create_class<N: Into<String>>(&mut self, name: N) -> ClassBuilder {
@@ -286,6 +802,7 @@ impl Container for Instance {
     fn create_attribute<N: Into<String>>(&mut self, name: N) -> AttributeBuilder {
         self.reactor
             .create_new_attribute(self.r_notifier.clone())
+            .with_parent(self.sub_elements.clone())
             .with_topic(format!("{}/{}", self.topic, name.into())) // take the device topic as root
     }
 
@@ -295,6 +812,42 @@ impl Container for Instance {
     where
         F: Future<Output = TaskResult> + Send + 'static,
     {
-        self.spawner.spawn_with_name(name, future.boxed()).unwrap();
+        //
+        // The future can be run only once, so it can never restart
+        let mut future = Some(future.boxed());
+        let factory: TaskFactory = Box::new(move || {
+            future
+                .take()
+                .unwrap_or_else(|| async { Err(Error::Wtf) }.boxed())
+        });
+        self.spawn_supervised(name.into(), RestartPolicy::Never, factory)
+            .await;
+    }
+
+    /// Override
+    ///
+    async fn spawn_with_policy<N: Send + Into<String>, F, Fut>(
+        &mut self,
+        name: N,
+        policy: RestartPolicy,
+        mut factory: F,
+    ) where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = TaskResult> + Send + 'static,
+    {
+        let factory: TaskFactory = Box::new(move || factory().boxed());
+        self.spawn_supervised(name.into(), policy, factory).await;
+    }
+
+    /// Override
+    ///
+    async fn report_warning(&mut self, error: Error) {
+        Instance::report_warning(self, error).await;
+    }
+
+    /// Override
+    ///
+    async fn clear_warning(&mut self) {
+        Instance::clear_warning(self).await;
     }
 }
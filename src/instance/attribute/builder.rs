@@ -1,13 +1,15 @@
 use super::server_si::SiAttServer;
+use crate::instance::element::{Element, SubElements};
+use crate::reactor::recorder::MessageRecorder;
 use crate::runtime::notification::attribute::{AttributeMode, AttributeNotification};
+use crate::runtime::notification::EnablementNotification;
+use crate::{log_warn, Logger, NotificationSender};
 use crate::{
     BooleanAttServer, EnumAttServer, Error, JsonAttServer, MemoryCommandAttServer, MessageClient,
     MessageDispatcher, NumberAttServer, StringAttServer,
 };
-use crate::{Class, Notification};
 use serde_json::json;
 use std::sync::Weak;
-use tokio::sync::mpsc::Sender;
 use tokio::sync::Mutex;
 
 #[derive(Clone)]
@@ -15,9 +17,9 @@ use tokio::sync::Mutex;
 /// Object that allow to build an generic attribute
 ///
 pub struct AttributeBuilder {
-    /// Parent class if any
+    /// Elements of the parent instance or class, the attribute is attached to it
     ///
-    parent_class: Option<Class>,
+    parent: Option<SubElements>,
 
     /// The mqtt client
     pub message_client: MessageClient,
@@ -26,8 +28,8 @@ pub struct AttributeBuilder {
     /// incoming messages on attributes
     pub message_dispatcher: Weak<Mutex<MessageDispatcher>>,
 
-    ///
-    pub r_notifier: Option<Sender<Notification>>,
+    /// To send the creation and alert notifications to the runtime
+    pub r_notifier: Option<NotificationSender>,
 
     /// Topic of the attribute
     pub topic: Option<String>,
@@ -42,18 +44,24 @@ pub struct AttributeBuilder {
     pub r#type: Option<String>,
 
     pub info: Option<String>,
+
+    /// Records the published values if any
+    pub recorder: Option<MessageRecorder>,
+
+    /// False if the attribute must start disabled (parent class disabled)
+    pub enabled: bool,
 }
 
 impl AttributeBuilder {
     /// Create a new builder
     pub fn new(
-        parent_class: Option<Class>,
+        parent: Option<SubElements>,
         message_client: MessageClient,
         message_dispatcher: Weak<Mutex<MessageDispatcher>>,
-        r_notifier: Option<Sender<Notification>>,
+        r_notifier: Option<NotificationSender>,
     ) -> AttributeBuilder {
         AttributeBuilder {
-            parent_class,
+            parent,
             message_client,
             message_dispatcher,
             r_notifier,
@@ -62,8 +70,31 @@ impl AttributeBuilder {
             mode: None,
             r#type: None,
             info: None,
+            recorder: None,
+            enabled: true,
         }
     }
+    /// Attach the attribute to the elements of its parent once finished
+    ///
+    pub fn with_parent(mut self, parent: SubElements) -> Self {
+        self.parent = Some(parent);
+        self
+    }
+
+    /// Record the values published by the attribute
+    ///
+    pub fn with_recorder(mut self, recorder: MessageRecorder) -> Self {
+        self.recorder = Some(recorder);
+        self
+    }
+
+    /// Start the attribute enabled or disabled, like its parent class
+    ///
+    pub fn with_enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
     /// Attach a topic
     pub fn with_topic<T: Into<String>>(mut self, topic: T) -> Self {
         self.topic = Some(topic.into());
@@ -103,7 +134,7 @@ impl AttributeBuilder {
     }
 
     ///
-    ///
+    /// Finish attribute building and configure it with 'si' type.
     ///
     pub async fn finish_as_si<N: Into<String>>(
         mut self,
@@ -124,7 +155,8 @@ impl AttributeBuilder {
         ));
         let att = SiAttServer::new(self.clone(), unit_string, min, max, decimals);
         att.inner.lock().await.init(att.inner.clone()).await?;
-        self.send_creation_notification();
+        self.send_creation_notification(att.logger()).await;
+        self.attach_to_parent(att.clone_as_element()).await;
         Ok(att)
     }
 
@@ -135,13 +167,8 @@ impl AttributeBuilder {
         self.r#type = Some(BooleanAttServer::r#type());
         let att = BooleanAttServer::new(self.clone());
         att.inner.lock().await.init(att.inner.clone()).await?;
-        self.send_creation_notification();
-
-        //
-        // Attach the attribute to its parent class if exist
-        if let Some(mut parent_class) = self.parent_class {
-            parent_class.push_sub_element(att.clone_as_element()).await;
-        }
+        self.send_creation_notification(att.logger()).await;
+        self.attach_to_parent(att.clone_as_element()).await;
 
         Ok(att)
     }
@@ -153,7 +180,8 @@ impl AttributeBuilder {
         self.r#type = Some(StringAttServer::r#type());
         let att = StringAttServer::new(self.clone());
         att.inner.lock().await.init(att.inner.clone()).await?;
-        self.send_creation_notification();
+        self.send_creation_notification(att.logger()).await;
+        self.attach_to_parent(att.clone_as_element()).await;
         Ok(att)
     }
 
@@ -181,7 +209,8 @@ impl AttributeBuilder {
         // Create server object
         let att = EnumAttServer::new(self.clone(), choices);
         att.inner.lock().await.init(att.inner.clone()).await?;
-        self.send_creation_notification();
+        self.send_creation_notification(att.logger()).await;
+        self.attach_to_parent(att.clone_as_element()).await;
         Ok(att)
     }
 
@@ -193,57 +222,67 @@ impl AttributeBuilder {
         let att = JsonAttServer::new(self.clone());
 
         att.inner.lock().await.init(att.inner.clone()).await?;
-        self.send_creation_notification();
-
-        //
-        // Attach the attribute to its parent class if exist
-        if let Some(mut parent_class) = self.parent_class {
-            parent_class.push_sub_element(att.clone_as_element()).await;
-        }
+        self.send_creation_notification(att.logger()).await;
+        self.attach_to_parent(att.clone_as_element()).await;
 
         Ok(att)
     }
 
-    ///
+    /// Finish attribute building and configure it with 'number' type.
     ///
     pub async fn finish_as_number(mut self) -> Result<NumberAttServer, Error> {
         self.r#type = Some(NumberAttServer::r#type());
         let att = NumberAttServer::new(self.clone());
         att.inner.lock().await.init(att.inner.clone()).await?;
-        self.send_creation_notification();
+        self.send_creation_notification(att.logger()).await;
+        self.attach_to_parent(att.clone_as_element()).await;
         Ok(att)
     }
 
-    ///
+    /// Finish attribute building and configure it with 'memory_command' type.
     ///
     pub async fn finish_as_memory_command(mut self) -> Result<MemoryCommandAttServer, Error> {
         self.r#type = Some(MemoryCommandAttServer::r#type());
         let att = MemoryCommandAttServer::new(self.clone());
         att.inner.lock().await.init(att.inner.clone()).await?;
-        self.send_creation_notification();
+        self.send_creation_notification(att.logger()).await;
+        self.attach_to_parent(att.clone_as_element()).await;
         Ok(att)
     }
 
     ///
+    /// Notify the runtime of the new attribute
     ///
+    /// The notification is coalesced when the channel is full, a failure (runtime over)
+    /// does not prevent the attribute to work so it is only logged.
     ///
-    fn send_creation_notification(&self) {
-        //
-        //
+    async fn send_creation_notification(&self, logger: &Logger) {
         let bis = self.topic.clone().unwrap();
         if let Some(r_notifier) = self.r_notifier.clone() {
-            r_notifier
-                .try_send(
-                    AttributeNotification::new(
-                        bis,
-                        self.r#type.clone().unwrap(),
-                        self.mode.clone().unwrap(),
-                        self.info.clone(),
-                        self.settings.clone(),
-                    )
-                    .into(),
-                )
-                .unwrap();
+            let notification = AttributeNotification::new(
+                bis,
+                self.r#type.clone().unwrap(),
+                self.mode.clone().unwrap(),
+                self.info.clone(),
+                self.settings.clone(),
+            );
+            if let Err(e) = r_notifier.send(notification.into()).await {
+                log_warn!(logger, "Fail to send attribute notification '{:?}'", e);
+            }
+            if !self.enabled {
+                let notification = EnablementNotification::new(self.topic.as_ref().unwrap(), false);
+                if let Err(e) = r_notifier.send(notification.into()).await {
+                    log_warn!(logger, "Fail to send attribute enablement '{:?}'", e);
+                }
+            }
+        }
+    }
+
+    /// Make the attribute reachable from its parent
+    ///
+    async fn attach_to_parent(&self, element: Element) {
+        if let Some(parent) = &self.parent {
+            parent.lock().await.push(element);
         }
     }
 }
@@ -1,4 +1,7 @@
+use crate::log_debug;
 use crate::log_trace;
+use crate::reactor::publish_enablement;
+use crate::reactor::recorder::MessageRecorder;
 use crate::runtime::notification::attribute::AttributeMode;
 use crate::runtime::notification::EnablementNotification;
 use crate::tracing::Logger;
@@ -10,12 +13,13 @@ use crate::MessageCodec;
 use crate::MessageDispatcher;
 use crate::MessageHandler;
 use crate::Notification;
+use crate::NotificationSender;
 use async_trait::async_trait;
 use bytes::Bytes;
 use rumqttc::QoS;
+use serde_json::json;
 use std::sync::Arc;
 use std::sync::Weak;
-use tokio::sync::mpsc::Sender;
 use tokio::sync::Mutex;
 use tokio::sync::Notify;
 
@@ -66,6 +70,11 @@ pub struct AttServer<TYPE: MessageCodec> {
     ///
     topic_att: String,
 
+    ///
+    /// The topic on which rejected commands are answered
+    ///
+    topic_error: String,
+
     ///
     /// Requested value of the attribute (set by the user)
     ///
@@ -76,7 +85,23 @@ pub struct AttServer<TYPE: MessageCodec> {
     ///
     _mode: AttributeMode,
 
-    r_notifier: Option<Sender<Notification>>,
+    r_notifier: Option<NotificationSender>,
+
+    /// Records the published values if any
+    ///
+    recorder: Option<MessageRecorder>,
+}
+
+/// Send a notification to the runtime, if there is a notifier
+///
+pub async fn notify_runtime(
+    r_notifier: &Option<NotificationSender>,
+    notification: Notification,
+) -> Result<(), Error> {
+    match r_notifier {
+        Some(r_notifier) => r_notifier.send(notification).await,
+        None => Ok(()),
+    }
 }
 
 impl<TYPE: MessageCodec> AttServer<TYPE> {
@@ -86,18 +111,45 @@ impl<TYPE: MessageCodec> AttServer<TYPE> {
     ///
     pub async fn init(&self, attribute: Arc<Mutex<dyn MessageHandler>>) -> Result<(), Error> {
         self.register(attribute).await?;
-        self.subscribe().await
+        self.subscribe().await?;
+        //
+        // Overwrite the enablement retained by a previous run
+        publish_enablement(&self.message_client, &self.topic, self.enabled).await
+    }
+
+    /// False if the attribute has been disabled
+    ///
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
     }
 
     ///
     /// Send a notification to the underscore device to raise an alert
     ///
-    pub fn send_alert(&self, message: String) {
-        if let Some(r_notifier) = self.r_notifier.clone() {
-            r_notifier
-                .try_send(AlertNotification::new(self.topic.clone(), message).into())
-                .unwrap();
-        }
+    pub async fn send_alert(&self, message: String) -> Result<(), Error> {
+        self.send_alert_notification(AlertNotification::new(self.topic.clone(), message))
+            .await
+    }
+
+    ///
+    /// Send a step of an alert lifecycle
+    ///
+    pub async fn send_alert_notification(&self, alert: AlertNotification) -> Result<(), Error> {
+        self.send_notification(alert.into()).await
+    }
+
+    ///
+    /// Send a notification to the runtime, if the attribute has one
+    ///
+    pub async fn send_notification(&self, notification: Notification) -> Result<(), Error> {
+        notify_runtime(&self.r_notifier, notification).await
+    }
+
+    ///
+    /// Notifier of the runtime, to send without holding the attribute
+    ///
+    pub fn r_notifier(&self) -> Option<NotificationSender> {
+        self.r_notifier.clone()
     }
 
     ///
@@ -109,7 +161,6 @@ impl<TYPE: MessageCodec> AttServer<TYPE> {
         self.message_client
             .subscribe(topic_att, QoS::AtMostOnce)
             .await
-            .map_err(|e| Error::MessageAttributeSubscribeError(e.to_string()))
     }
 
     ///
@@ -167,7 +218,11 @@ impl<TYPE: MessageCodec> AttServer<TYPE> {
         // }
 
         // Set the requested value and publish the request
+        // A disabled attribute keeps it until it is enabled again
         self.requested_value = Some(new_value);
+        if !self.enabled {
+            return Ok(());
+        }
         match self.requested_value.clone() {
             Some(requested_value) => {
                 self.publish(requested_value.into_message_payload()?)
@@ -188,20 +243,37 @@ impl<TYPE: MessageCodec> AttServer<TYPE> {
         V: Into<Vec<u8>>,
     {
         let value = value.into();
-        let pyl_size = value.len();
+
+        if let Some(recorder) = &self.recorder {
+            if let Err(e) = recorder.record_value(&self.topic_att, &value) {
+                log_debug!(self.logger, "cannot record value {:?}", e);
+            }
+        }
 
         self.message_client
             .publish(&self.topic_att, QoS::AtMostOnce, true, value)
             .await
-            .map_err(|e| Error::PublishError {
-                topic: self.topic_att.clone(),
-                pyl_size: pyl_size,
-                cause: e.to_string(),
-            })
+    }
+
+    /// Answer a rejected command on the '{topic}/error' topic
+    ///
+    pub async fn publish_error(&self, error: &Error) -> Result<(), Error> {
+        let payload = json!({
+            "error": error.to_string(),
+            "details": format!("{:?}", error),
+        })
+        .to_string();
+        self.message_client
+            .publish(&self.topic_error, QoS::AtMostOnce, false, payload)
+            .await
     }
 
     /// Request attribute server disabling
     ///
+    /// A disabled attribute rejects the commands and its retained value is
+    /// removed, so clients cannot read a stale value. The last value is
+    /// published again when the attribute is enabled.
+    ///
     pub async fn change_enablement(&mut self, enabled: bool) -> Result<(), Error> {
         //
         // TRACE
@@ -214,19 +286,21 @@ impl<TYPE: MessageCodec> AttServer<TYPE> {
 
         //
         // Do action
+        let changed = self.enabled != enabled;
         self.enabled = enabled;
+        if changed {
+            match (enabled, self.requested_value.clone()) {
+                (true, Some(value)) => self.publish(value.into_message_payload()?).await?,
+                (true, None) => {}
+                (false, _) => self.publish(Vec::new()).await?,
+            }
+        }
 
         //
-        // Send a notification if possible
-        if let Some(notification_sender) = self.r_notifier.clone() {
-            notification_sender
-                .try_send(EnablementNotification::new(&self.topic, self.enabled).into())
-                .map_err(|e| {
-                    Error::InternalLogic(format!("fail to push platform notification ({:?})", e))
-                })
-        } else {
-            Ok(())
-        }
+        // Share it with all clients and with the runtime
+        publish_enablement(&self.message_client, &self.topic, self.enabled).await?;
+        self.send_notification(EnablementNotification::new(&self.topic, self.enabled).into())
+            .await
     }
 }
 
@@ -236,7 +310,22 @@ impl<TYPE: MessageCodec> MessageHandler for AttServer<TYPE> {
     /// On message, just deserialize then push into the fifo
     ///
     async fn on_message(&mut self, data: &Bytes) -> Result<(), Error> {
-        let in_value = TYPE::from_message_payload(data)?;
+        if !self.enabled {
+            log_debug!(self.logger, "command rejected, attribute disabled");
+            let error = Error::Disabled(self.topic.clone());
+            return self.publish_error(&error).await;
+        }
+        let in_value = match TYPE::from_message_payload(data) {
+            Ok(in_value) => in_value,
+            Err(e) => {
+                //
+                // The sender must know why its command is ignored
+                if let Err(publish_error) = self.publish_error(&e).await {
+                    log_debug!(self.logger, "fail to answer the error {:?}", publish_error);
+                }
+                return Err(e);
+            }
+        };
         self.in_queue.push(in_value);
         self.in_notifier.notify_waiters();
         Ok(())
@@ -251,7 +340,7 @@ impl<TYPE: MessageCodec> From<AttributeBuilder> for AttServer<TYPE> {
         let topic = builder.topic.as_ref().unwrap().clone();
         Self {
             logger: Logger::new_for_attribute_from_topic(topic.clone()),
-            enabled: true, // enabled by default
+            enabled: builder.enabled, // disabled if created under a disabled class
             message_dispatcher: builder.message_dispatcher,
             message_client: builder.message_client,
             topic: topic.clone(),
@@ -259,9 +348,11 @@ impl<TYPE: MessageCodec> From<AttributeBuilder> for AttServer<TYPE> {
             last_popped_value: None,
             in_notifier: Arc::new(Notify::new()),
             topic_att: format!("{}/att", topic.clone()),
+            topic_error: format!("{}/error", topic.clone()),
             requested_value: None,
             _mode: builder.mode.unwrap(),
             r_notifier: builder.r_notifier,
+            recorder: builder.recorder,
         }
     }
 }
@@ -281,6 +372,12 @@ macro_rules! generic_att_server_methods {
             &self.logger
         }
 
+        /// Topic of the attribute
+        ///
+        pub async fn topic(&self) -> String {
+            self.inner.lock().await.topic.clone()
+        }
+
         /// Bloc until at least a command is received
         ///
         pub async fn wait_commands(&self) {
@@ -299,10 +396,40 @@ macro_rules! generic_att_server_methods {
             function.await
         }
 
+        /// Topic and runtime notifier, cloned to send alerts without holding the attribute
+        ///
+        async fn alert_target(&self) -> (String, Option<$crate::NotificationSender>) {
+            let inner = self.inner.lock().await;
+            (inner.topic.clone(), inner.r_notifier())
+        }
+
         ///
         ///
-        pub async fn send_alert<T: Into<String>>(&self, message: T) {
-            self.inner.lock().await.send_alert(message.into());
+        pub async fn send_alert<T: Into<String>>(&self, message: T) -> Result<(), Error> {
+            let (topic, r_notifier) = self.alert_target().await;
+            let alert = $crate::AlertNotification::new(topic, message.into());
+            $crate::instance::attribute::server::notify_runtime(&r_notifier, alert.into()).await
+        }
+
+        /// Raise the condition 'code' on this attribute, until 'clear_alert'
+        ///
+        pub async fn raise_alert<C: Into<String>, M: Into<String>>(
+            &self,
+            severity: $crate::AlertSeverity,
+            code: C,
+            message: M,
+        ) -> Result<(), Error> {
+            let (topic, r_notifier) = self.alert_target().await;
+            let alert = $crate::AlertNotification::raised(topic, severity, code, message);
+            $crate::instance::attribute::server::notify_runtime(&r_notifier, alert.into()).await
+        }
+
+        /// The condition 'code' raised on this attribute is over
+        ///
+        pub async fn clear_alert<C: Into<String>>(&self, code: C) -> Result<(), Error> {
+            let (topic, r_notifier) = self.alert_target().await;
+            let alert = $crate::AlertNotification::cleared(topic, code);
+            $crate::instance::attribute::server::notify_runtime(&r_notifier, alert.into()).await
         }
 
         /// Request attribute server enablement
@@ -324,3 +451,41 @@ macro_rules! generic_att_server_methods {
         }
     };
 }
+
+#[cfg(test)]
+mod tests {
+    use super::AttServer;
+    use crate::reactor::loopback::{LoopbackBroker, LoopbackClient};
+    use crate::{AttributeBuilder, BooleanCodec, MessageHandler};
+    use bytes::Bytes;
+    use std::sync::Weak;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_disabled_attribute_rejects_commands() {
+        let broker = LoopbackBroker::new();
+        let mut user = LoopbackClient::new(&broker);
+        user.subscribe("pza/dev/att/error");
+        let builder = AttributeBuilder::new(None, user.message_client(), Weak::new(), None)
+            .with_topic("pza/dev/att")
+            .with_rw();
+        let mut att = AttServer::<BooleanCodec>::from(builder);
+
+        att.on_message(&Bytes::from("true")).await.unwrap();
+        assert_eq!(att.in_queue.len(), 1);
+        att.set(true.into()).await.unwrap();
+        assert!(broker.retained("pza/dev/att/att").is_some());
+
+        //
+        // Disabled: value no longer available and commands answered with an error
+        att.change_enablement(false).await.unwrap();
+        assert!(broker.retained("pza/dev/att/att").is_none());
+        assert_eq!(broker.retained("pza/dev/att/enabled").unwrap(), "false");
+        att.on_message(&Bytes::from("false")).await.unwrap();
+        assert_eq!(att.in_queue.len(), 1);
+        let error = user.next_message(Duration::from_millis(100)).await.unwrap();
+        assert_eq!(error.topic, "pza/dev/att/error");
+        let payload: serde_json::Value = serde_json::from_slice(&error.payload).unwrap();
+        assert_eq!(payload["error"], "'pza/dev/att' is disabled");
+    }
+}
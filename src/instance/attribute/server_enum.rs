@@ -1,5 +1,8 @@
 use super::server::AttServer;
-use crate::{generic_att_server_methods, AttributeBuilder, Error, Logger, StringCodec};
+use crate::{
+    generic_att_server_methods, instance::element::Element, AttributeBuilder, Error, Logger,
+    StringCodec,
+};
 
 use std::{future::Future, sync::Arc};
 use tokio::sync::Mutex;
@@ -28,6 +31,12 @@ impl EnumAttServer {
     // Require inner member
     generic_att_server_methods!();
 
+    /// Clone as an element object
+    ///
+    pub fn clone_as_element(&self) -> Element {
+        Element::AsEnum(self.clone())
+    }
+
     ///
     ///
     ///
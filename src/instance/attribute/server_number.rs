@@ -2,7 +2,10 @@ use std::{future::Future, sync::Arc};
 use tokio::sync::Mutex;
 
 use super::server::AttServer;
-use crate::{generic_att_server_methods, AttributeBuilder, Error, Logger, NumberCodec};
+use crate::{
+    generic_att_server_methods, instance::element::Element, AttributeBuilder, Error, Logger,
+    NumberCodec,
+};
 
 ///
 ///
@@ -23,6 +26,12 @@ impl NumberAttServer {
     // Require inner member
     generic_att_server_methods!();
 
+    /// Clone as an element object
+    ///
+    pub fn clone_as_element(&self) -> Element {
+        Element::AsNumber(self.clone())
+    }
+
     ///
     ///
     pub fn r#type() -> String {
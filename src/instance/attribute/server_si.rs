@@ -2,7 +2,10 @@ use std::{future::Future, sync::Arc};
 use tokio::sync::Mutex;
 
 use super::server::AttServer;
-use crate::{generic_att_server_methods, AttributeBuilder, Error, Logger, SiCodec, StableNumber};
+use crate::{
+    generic_att_server_methods, instance::element::Element, AttributeBuilder, Error, Logger,
+    SiCodec, StableNumber,
+};
 
 ///
 ///
@@ -29,6 +32,12 @@ impl SiAttServer {
     // Require inner member
     generic_att_server_methods!();
 
+    /// Clone as an element object
+    ///
+    pub fn clone_as_element(&self) -> Element {
+        Element::AsSi(self.clone())
+    }
+
     ///
     ///
     pub fn r#type() -> String {
@@ -1,6 +1,9 @@
 use super::server::AttServer;
 
-use crate::{generic_att_server_methods, AttributeBuilder, Error, Logger, StringCodec};
+use crate::{
+    generic_att_server_methods, instance::element::Element, AttributeBuilder, Error, Logger,
+    StringCodec,
+};
 
 use std::{future::Future, sync::Arc};
 use tokio::sync::Mutex;
@@ -23,6 +26,12 @@ impl StringAttServer {
     // Require inner member
     generic_att_server_methods!();
 
+    /// Clone as an element object
+    ///
+    pub fn clone_as_element(&self) -> Element {
+        Element::AsString(self.clone())
+    }
+
     ///
     ///
     pub fn r#type() -> String {
@@ -1,5 +1,7 @@
-use super::element::Element;
+use super::element::{Element, SubElements};
+use super::task_registry::RestartPolicy;
 use super::{class_builder::ClassBuilder, Container};
+use crate::runtime::notification::EnablementNotification;
 use crate::{AttributeBuilder, Error, Instance, Logger, TaskResult};
 use async_trait::async_trait;
 use futures::lock::Mutex;
@@ -30,7 +32,7 @@ pub struct Class {
 
     /// Sub elements
     ///
-    sub_elements: Arc<Mutex<Vec<Element>>>,
+    sub_elements: SubElements,
 }
 
 impl Class {
@@ -41,7 +43,7 @@ impl Class {
             logger: builder.device.logger.new_for_class(&builder.topic),
             instance: builder.device.clone(),
             topic: builder.topic.clone(),
-            enabled: Arc::new(AtomicBool::new(true)),
+            enabled: Arc::new(AtomicBool::new(builder.parent_enabled())),
             sub_elements: Arc::new(Mutex::new(Vec::new())),
         }
     }
@@ -49,7 +51,7 @@ impl Class {
     /// Clone as an element object
     ///
     pub fn clone_as_element(&self) -> Element {
-        Element::Class(self.clone())
+        Element::Class(Box::new(self.clone()))
     }
 
     /// Append a new sub element
@@ -58,10 +60,33 @@ impl Class {
         self.sub_elements.lock().await.push(element);
     }
 
+    /// Topic of the class
+    ///
+    pub fn topic(&self) -> &String {
+        &self.topic
+    }
+
+    /// False if the class has been disabled
+    ///
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Enable or disable the class and all its sub elements
+    ///
+    /// The attributes of a disabled class reject the commands.
+    ///
     pub async fn change_enablement(&mut self, enabled: bool) -> Result<(), Error> {
         //
         // Flag local variable
         self.enabled.store(enabled, Ordering::Relaxed);
+        self.instance
+            .reactor
+            .publish_enablement(&self.topic, enabled)
+            .await?;
+        self.instance
+            .send_notification(EnablementNotification::new(&self.topic, enabled).into())
+            .await?;
 
         //
         // Also change sub elements
@@ -83,6 +108,12 @@ impl Container for Class {
         &self.logger
     }
 
+    /// Override
+    ///
+    fn sub_elements(&self) -> SubElements {
+        self.sub_elements.clone()
+    }
+
     /// Override
     ///
     fn create_class<N: Into<String>>(&mut self, name: N) -> ClassBuilder {
@@ -100,6 +131,8 @@ impl Container for Class {
         self.instance
             .reactor
             .create_new_attribute(self.instance.r_notifier.clone())
+            .with_parent(self.sub_elements.clone())
+            .with_enabled(self.is_enabled())
             .with_topic(format!("{}/{}", self.topic, name.into()))
     }
 
@@ -111,4 +144,30 @@ impl Container for Class {
     {
         self.instance.spawn(name, future).await;
     }
+
+    /// Override
+    ///
+    async fn spawn_with_policy<N: Send + Into<String>, F, Fut>(
+        &mut self,
+        name: N,
+        policy: RestartPolicy,
+        factory: F,
+    ) where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = TaskResult> + Send + 'static,
+    {
+        self.instance.spawn_with_policy(name, policy, factory).await;
+    }
+
+    /// Override
+    ///
+    async fn report_warning(&mut self, error: Error) {
+        self.instance.report_warning(error).await;
+    }
+
+    /// Override
+    ///
+    async fn clear_warning(&mut self) {
+        self.instance.clear_warning().await;
+    }
 }
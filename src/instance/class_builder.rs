@@ -1,6 +1,7 @@
-use crate::{Class, ClassNotification, Reactor};
+use crate::runtime::notification::EnablementNotification;
+use crate::{log_warn, Class, ClassNotification, Reactor};
 
-use super::Instance;
+use super::{Container, Instance};
 
 pub struct ClassBuilder {
     /// Parent class if any
@@ -20,6 +21,22 @@ pub struct ClassBuilder {
     pub topic: String,
 
     pub tags: Vec<String>,
+
+    /// Description for the user
+    ///
+    pub info: Option<String>,
+
+    /// Name displayed instead of the topic layer
+    ///
+    pub display_name: Option<String>,
+
+    /// Position hint among the sibling classes
+    ///
+    pub order: Option<i32>,
+
+    /// Class specific settings
+    ///
+    pub settings: Option<serde_json::Value>,
 }
 
 impl ClassBuilder {
@@ -37,6 +54,10 @@ impl ClassBuilder {
             // device_dyn_info: device_dyn_info,
             topic: topic.into(),
             tags: Vec::new(),
+            info: None,
+            display_name: None,
+            order: None,
+            settings: None,
         }
     }
 
@@ -45,25 +66,99 @@ impl ClassBuilder {
         self
     }
 
+    /// Describe the class for the user
+    ///
+    pub fn with_info<T: Into<String>>(mut self, info: T) -> Self {
+        self.info = Some(info.into());
+        self
+    }
+
+    /// Name displayed by the user interfaces instead of the topic layer
+    ///
+    pub fn with_display_name<T: Into<String>>(mut self, display_name: T) -> Self {
+        self.display_name = Some(display_name.into());
+        self
+    }
+
+    /// Position of the class among its siblings, lower first
+    ///
+    pub fn with_order(mut self, order: i32) -> Self {
+        self.order = Some(order);
+        self
+    }
+
+    /// Attach settings to the class
+    ///
+    pub fn with_settings(mut self, settings: serde_json::Value) -> Self {
+        self.settings = Some(settings);
+        self
+    }
+
+    /// False if the class is created under a disabled class, it starts disabled too
+    ///
+    pub fn parent_enabled(&self) -> bool {
+        self.parent_class
+            .as_ref()
+            .map(|parent| parent.is_enabled())
+            .unwrap_or(true)
+    }
+
     ///
     ///
     ///
     pub async fn finish(self) -> Class {
+        let enabled = self.parent_enabled();
         let bis = self.topic.clone();
-        if let Some(r_notifier) = self.device.r_notifier.clone() {
-            r_notifier
-                .try_send(ClassNotification::new(bis, self.tags.clone()).into())
-                .unwrap();
+        let notification = ClassNotification::new(bis, self.tags.clone())
+            .with_info(self.info.clone())
+            .with_display_name(self.display_name.clone())
+            .with_order(self.order)
+            .with_settings(self.settings.clone());
+        if let Err(e) = self.device.send_notification(notification.into()).await {
+            log_warn!(
+                self.device.logger,
+                "Fail to send class notification '{:?}'",
+                e
+            );
+        }
+        if !enabled {
+            let notification = EnablementNotification::new(&self.topic, false);
+            if let Err(e) = self.device.send_notification(notification.into()).await {
+                log_warn!(
+                    self.device.logger,
+                    "Fail to send class enablement '{:?}'",
+                    e
+                );
+            }
         }
+        //
+        // Overwrite the enablement retained by a previous run
+        if let Err(e) = self.reactor.publish_enablement(&self.topic, enabled).await {
+            log_warn!(
+                self.device.logger,
+                "Fail to publish class enablement '{:?}'",
+                e
+            );
+        }
+
         // insert in status
         let class = Class::new(&self);
 
         //
-        // Attach the attribute to its parent class if exist
-        if let Some(mut parent_class) = self.parent_class {
-            parent_class
-                .push_sub_element(class.clone_as_element())
-                .await;
+        // Attach the class to its parent class, or to the instance root
+        match self.parent_class {
+            Some(mut parent_class) => {
+                parent_class
+                    .push_sub_element(class.clone_as_element())
+                    .await;
+            }
+            None => {
+                self.device
+                    .sub_elements()
+                    .lock()
+                    .await
+                    .push(class.clone_as_element());
+            }
         }
 
         class
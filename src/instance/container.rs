@@ -1,4 +1,6 @@
-use crate::{AttributeBuilder, ClassBuilder, Logger, TaskResult};
+use super::element::{find_element, Element, SubElements};
+use super::task_registry::RestartPolicy;
+use crate::{AttributeBuilder, Class, ClassBuilder, Error, Logger, TaskResult};
 use async_trait::async_trait;
 use std::future::Future;
 
@@ -7,11 +9,53 @@ use std::future::Future;
 ///
 /// It allows parent container abstraction, Instance can be seen as the top level class
 ///
-pub trait Container: Clone {
+pub trait Container: Clone + Send + Sync {
     /// Get for the container logger
     ///
     fn logger(&self) -> &Logger;
 
+    /// Classes and attributes created directly under this container
+    ///
+    fn sub_elements(&self) -> SubElements;
+
+    /// Direct children of the container, in creation order
+    ///
+    async fn children(&self) -> Vec<Element> {
+        self.sub_elements().lock().await.clone()
+    }
+
+    /// Find the element at the relative 'path' (ex: "channel/0/voltage")
+    ///
+    async fn find_element(&self, path: &str) -> Option<Element> {
+        find_element(&self.sub_elements(), path).await
+    }
+
+    /// Find the class at the relative 'path'
+    ///
+    async fn find_class(&self, path: &str) -> Option<Class> {
+        match self.find_element(path).await {
+            Some(Element::Class(class)) => Some(*class),
+            _ => None,
+        }
+    }
+
+    /// Find the attribute at the relative 'path'
+    ///
+    async fn find_attribute(&self, path: &str) -> Option<Element> {
+        self.find_element(path)
+            .await
+            .filter(|element| !element.is_class())
+    }
+
+    /// Enable or disable the element at the relative 'path', with its sub tree
+    ///
+    async fn change_enablement_of(&self, path: &str, enabled: bool) -> Result<(), Error> {
+        match self.find_element(path).await {
+            Some(mut element) => element.change_enablement(enabled).await,
+            None => Err(Error::InvalidArgument(format!("no element at '{}'", path))),
+        }
+    }
+
     /// Create a new interface from this device
     ///
     fn create_class<N: Into<String>>(&mut self, name: N) -> ClassBuilder;
@@ -25,4 +69,25 @@ pub trait Container: Clone {
     async fn spawn<N: Send + Into<String>, F>(&mut self, name: N, future: F)
     where
         F: Future<Output = TaskResult> + Send + 'static;
+
+    /// Spawn a new supervised task inside this instance
+    ///
+    /// 'factory' is called to build the task future on each (re)start
+    ///
+    async fn spawn_with_policy<N: Send + Into<String>, F, Fut>(
+        &mut self,
+        name: N,
+        policy: RestartPolicy,
+        factory: F,
+    ) where
+        F: FnMut() -> Fut + Send + 'static,
+        Fut: Future<Output = TaskResult> + Send + 'static;
+
+    /// Report a non-fatal error, the instance moves into the Warning state
+    ///
+    async fn report_warning(&mut self, error: Error);
+
+    /// The reported non-fatal error is over, the instance goes back to Running
+    ///
+    async fn clear_warning(&mut self);
 }
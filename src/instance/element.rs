@@ -1,23 +1,148 @@
-use crate::{BooleanAttServer, Class, Error, JsonAttServer};
+use super::Container;
+use crate::{
+    BooleanAttServer, Class, EnumAttServer, Error, JsonAttServer, MemoryCommandAttServer,
+    NumberAttServer, SiAttServer, StringAttServer,
+};
+use futures::future::BoxFuture;
+use futures::lock::Mutex;
+use futures::FutureExt;
+use std::sync::Arc;
+
+/// Elements (classes and attributes) attached to an instance or a class
+///
+pub type SubElements = Arc<Mutex<Vec<Element>>>;
 
 #[derive(Clone)]
 pub enum Element {
-    Class(Class),
+    Class(Box<Class>),
     AsBoolean(BooleanAttServer),
+    AsEnum(EnumAttServer),
     AsJson(JsonAttServer),
+    AsMemoryCommand(MemoryCommandAttServer),
+    AsNumber(NumberAttServer),
+    AsSi(SiAttServer),
+    AsString(StringAttServer),
 }
 
 impl Element {
-    /// Request attribute server enablement
+    /// Topic of the element
     ///
-    pub async fn change_enablement(&mut self, enabled: bool) -> Result<(), Error> {
+    pub async fn topic(&self) -> String {
         match self {
-            Element::Class(_class) => Ok(()),
-            Element::AsBoolean(att_server) => att_server.change_enablement(enabled).await,
-            Element::AsJson(att_server) => att_server.change_enablement(enabled).await,
+            Element::Class(class) => class.topic().clone(),
+            Element::AsBoolean(att_server) => att_server.topic().await,
+            Element::AsEnum(att_server) => att_server.topic().await,
+            Element::AsJson(att_server) => att_server.topic().await,
+            Element::AsMemoryCommand(att_server) => att_server.topic().await,
+            Element::AsNumber(att_server) => att_server.topic().await,
+            Element::AsSi(att_server) => att_server.topic().await,
+            Element::AsString(att_server) => att_server.topic().await,
         }
     }
 
+    /// Name of the element, the last layer of its topic
+    ///
+    pub async fn name(&self) -> String {
+        let topic = self.topic().await;
+        topic.rsplit('/').next().unwrap_or_default().to_string()
+    }
+
+    /// True if the element is a class
+    ///
+    pub fn is_class(&self) -> bool {
+        matches!(self, Element::Class(_))
+    }
+
+    /// The element if it is a class
+    ///
+    pub fn as_class(&self) -> Option<Class> {
+        match self {
+            Element::Class(class) => Some(class.as_ref().clone()),
+            _ => None,
+        }
+    }
+
+    /// The element if it is a boolean attribute
+    ///
+    pub fn as_boolean(&self) -> Option<BooleanAttServer> {
+        match self {
+            Element::AsBoolean(att_server) => Some(att_server.clone()),
+            _ => None,
+        }
+    }
+
+    /// The element if it is an enum attribute
+    ///
+    pub fn as_enum(&self) -> Option<EnumAttServer> {
+        match self {
+            Element::AsEnum(att_server) => Some(att_server.clone()),
+            _ => None,
+        }
+    }
+
+    /// The element if it is a json attribute
+    ///
+    pub fn as_json(&self) -> Option<JsonAttServer> {
+        match self {
+            Element::AsJson(att_server) => Some(att_server.clone()),
+            _ => None,
+        }
+    }
+
+    /// The element if it is a memory command attribute
+    ///
+    pub fn as_memory_command(&self) -> Option<MemoryCommandAttServer> {
+        match self {
+            Element::AsMemoryCommand(att_server) => Some(att_server.clone()),
+            _ => None,
+        }
+    }
+
+    /// The element if it is a number attribute
+    ///
+    pub fn as_number(&self) -> Option<NumberAttServer> {
+        match self {
+            Element::AsNumber(att_server) => Some(att_server.clone()),
+            _ => None,
+        }
+    }
+
+    /// The element if it is a si attribute
+    ///
+    pub fn as_si(&self) -> Option<SiAttServer> {
+        match self {
+            Element::AsSi(att_server) => Some(att_server.clone()),
+            _ => None,
+        }
+    }
+
+    /// The element if it is a string attribute
+    ///
+    pub fn as_string(&self) -> Option<StringAttServer> {
+        match self {
+            Element::AsString(att_server) => Some(att_server.clone()),
+            _ => None,
+        }
+    }
+
+    /// Request attribute server enablement, or of the whole sub tree for a class
+    ///
+    pub fn change_enablement(&mut self, enabled: bool) -> BoxFuture<'_, Result<(), Error>> {
+        async move {
+            match self {
+                Element::Class(class) => class.change_enablement(enabled).await,
+                Element::AsBoolean(att_server) => att_server.change_enablement(enabled).await,
+                Element::AsEnum(att_server) => att_server.change_enablement(enabled).await,
+                Element::AsJson(att_server) => att_server.change_enablement(enabled).await,
+                Element::AsMemoryCommand(att_server) => att_server.change_enablement(enabled).await,
+                Element::AsNumber(att_server) => att_server.change_enablement(enabled).await,
+                Element::AsSi(att_server) => att_server.change_enablement(enabled).await,
+                Element::AsString(att_server) => att_server.change_enablement(enabled).await,
+            }
+        }
+        .boxed()
+    }
+
     /// Request attribute server enablement
     ///
     pub async fn enable(&mut self) -> Result<(), Error> {
@@ -30,3 +155,30 @@ impl Element {
         self.change_enablement(false).await
     }
 }
+
+/// Find the element at 'path' (ex: "channel/0/voltage") under 'elements'
+///
+pub async fn find_element(elements: &SubElements, path: &str) -> Option<Element> {
+    let mut current = elements.clone();
+    let mut found = None;
+    for layer in path.split('/').filter(|layer| !layer.is_empty()) {
+        //
+        // Only classes have children
+        if let Some(element) = found.take() {
+            match element {
+                Element::Class(class) => current = class.sub_elements(),
+                _ => return None,
+            }
+        }
+        let children = current.lock().await.clone();
+        let mut next = None;
+        for child in children {
+            if child.name().await == layer {
+                next = Some(child);
+                break;
+            }
+        }
+        found = Some(next?);
+    }
+    found
+}
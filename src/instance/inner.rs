@@ -1,4 +1,4 @@
-use crate::{InstanceSettings, Reactor};
+use crate::{InstanceSettings, JsonAttServer, Reactor, StringAttServer};
 use std::sync::Arc;
 use tokio::sync::Mutex;
 
@@ -11,6 +11,26 @@ pub struct InstanceInner {
     /// Settings of the device, provided by the user
     ///
     pub settings: Option<InstanceSettings>,
+
+    /// Last error that moved the instance into the Error state
+    ///
+    pub last_error: Option<String>,
+
+    /// True while a warning reported by the driver (not by a task) is active
+    ///
+    pub warning_reported: bool,
+
+    /// Attribute '_/state' that publish the state of the instance
+    ///
+    pub att_state: Option<StringAttServer>,
+
+    /// Attribute '_/error' that publish the last error of the instance
+    ///
+    pub att_error: Option<StringAttServer>,
+
+    /// Attribute '_/tasks' that publish the health of the instance tasks
+    ///
+    pub att_tasks: Option<JsonAttServer>,
 }
 
 impl InstanceInner {
@@ -18,6 +38,11 @@ impl InstanceInner {
         InstanceInner {
             reactor: reactor,
             settings: settings,
+            last_error: None,
+            warning_reported: false,
+            att_state: None,
+            att_error: None,
+            att_tasks: None,
         }
     }
 }
@@ -1,10 +1,9 @@
-use super::Instance;
+use super::{Instance, State};
 use crate::task_channel::create_task_channel;
 use crate::{log_debug, log_warn, DriverOperations, Reactor, TaskReceiver};
-use crate::{Error, Notification, ProductionOrder};
+use crate::{Error, NotificationSender, ProductionOrder};
 use std::sync::Arc;
 use std::time::Duration;
-use tokio::sync::mpsc::Sender;
 use tokio::sync::Notify;
 use tokio::time::sleep;
 use tokio::{sync::Mutex, task::JoinSet};
@@ -25,6 +24,10 @@ pub struct InstanceMonitor {
     subtask_receiver: Arc<Mutex<TaskReceiver<DeviceTaskResult>>>,
 
     subtask_pool_not_empty_notifier: Arc<Notify>,
+
+    /// Triggered by the instance when its subtasks must be cleaned
+    ///
+    clean_request_notifier: Arc<Notify>,
 }
 
 impl InstanceMonitor {
@@ -32,7 +35,7 @@ impl InstanceMonitor {
     /// Constructor
     pub fn new(
         reactor: Reactor,
-        r_notifier: Option<Sender<Notification>>,
+        r_notifier: Option<NotificationSender>,
         operations: Box<dyn DriverOperations>,
         production_order: ProductionOrder,
     ) -> (InstanceMonitor, Instance) {
@@ -60,6 +63,7 @@ impl InstanceMonitor {
             subtask_pool: JoinSet::new(),
             subtask_receiver: Arc::new(Mutex::new(task_rx)),
             subtask_pool_not_empty_notifier: Arc::new(Notify::new()),
+            clean_request_notifier: device.clean_request_notifier(),
         };
         //
         // Ok
@@ -70,6 +74,7 @@ impl InstanceMonitor {
         let subtask_receiver_clone = self.subtask_receiver.clone();
         let mut subtask_receiver_clone_lock = subtask_receiver_clone.lock().await;
         let subtask_pool_not_empty_notifier_clone = self.subtask_pool_not_empty_notifier.clone();
+        let clean_request_notifier_clone = self.clean_request_notifier.clone();
         loop {
             tokio::select! {
                 //
@@ -90,6 +95,17 @@ impl InstanceMonitor {
 
                 },
                 //
+                // Abort subtasks before a reinitialization of the instance
+                //
+                _ = clean_request_notifier_clone.notified() => {
+                    self.clean_all_tasks().await;
+                    if let State::Stopping = self.device.state().await {
+                        self.device.notify_stopped();
+                        break;
+                    }
+                    self.device.move_to_state(State::Initializating).await;
+                },
+                //
                 //
                 //
                 _ = self.end_of_all_tasks() => {
@@ -102,6 +118,15 @@ impl InstanceMonitor {
         }
     }
 
+    /// Abort all the subtasks and wait for their end
+    ///
+    async fn clean_all_tasks(&mut self) {
+        self.subtask_pool.abort_all();
+        while self.subtask_pool.join_next().await.is_some() {}
+        self.device.abort_tasks(true).await;
+        log_debug!(self.device.logger, "All sub tasks cleaned");
+    }
+
     /// Wait for all tasks to complete
     ///
     async fn end_of_all_tasks(&mut self) {
@@ -119,16 +144,22 @@ impl InstanceMonitor {
                     Ok(_) => {
                         println!("Task completed");
                     }
+                    Err(e) if e.is_recoverable() => {
+                        //
+                        // The task is over but the instance can keep running
+                        self.device.report_warning(e).await;
+                    }
                     Err(e) => {
                         //
-                        // Debug log when the sub task crash
+                        // A fatal error, the instance must be mounted again
                         self.device
                             .logger
                             .error(format!("Instance sub task crash: {}", e));
 
                         self.subtask_pool.abort_all();
+                        self.device.abort_tasks(false).await;
 
-                        self.device.go_error().await;
+                        self.device.go_error_with_message(format!("{:?}", e)).await;
                     }
                 },
                 Err(e) => {
@@ -0,0 +1,296 @@
+use crate::TaskResult;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::time::Duration;
+
+/// Delay before the first restart of a supervised task, doubled on each consecutive restart
+///
+pub const TASK_RESTART_BASE_DELAY: Duration = Duration::from_millis(500);
+
+/// Maximal delay between two restarts, a task that ran longer is considered stable again
+///
+pub const TASK_RESTART_MAX_DELAY: Duration = Duration::from_secs(30);
+
+/// Number of consecutive restarts after which the task is given up
+///
+pub const TASK_MAX_RESTARTS: u32 = 8;
+
+/// What to do when a supervised task ends
+///
+#[derive(Default, Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum RestartPolicy {
+    /// The task is never restarted
+    ///
+    #[default]
+    Never,
+
+    /// The task is restarted only when it returns an error
+    ///
+    OnFailure,
+
+    /// The task is restarted each time it ends
+    ///
+    Always,
+}
+
+impl RestartPolicy {
+    /// True if the task must be restarted after this result
+    ///
+    pub fn must_restart(&self, result: &TaskResult) -> bool {
+        match self {
+            RestartPolicy::Never => false,
+            RestartPolicy::OnFailure => result.is_err(),
+            RestartPolicy::Always => true,
+        }
+    }
+}
+
+/// States of a task spawned inside an instance
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum TaskState {
+    Running,
+    Restarting,
+    Completed,
+    Failed,
+    Aborted,
+}
+
+/// Health information about a task spawned inside an instance
+///
+#[derive(Debug, Clone)]
+pub struct TaskInfo {
+    /// Name given when the task has been spawned
+    ///
+    pub name: String,
+
+    /// Restart policy of the task
+    ///
+    pub policy: RestartPolicy,
+
+    /// Current state
+    ///
+    pub state: TaskState,
+
+    /// Time of the last (re)start
+    ///
+    pub start_time: DateTime<Utc>,
+
+    /// Number of time the task has been restarted
+    ///
+    pub restart_count: u32,
+
+    /// Number of restarts without a stable run between them, drives the backoff
+    ///
+    pub consecutive_restarts: u32,
+
+    /// Last error returned by the task
+    ///
+    pub last_error: Option<String>,
+
+    /// Recoverable error reported by the task and not cleared yet
+    ///
+    pub warning: Option<String>,
+}
+
+impl TaskInfo {
+    /// Json representation published on the '_/tasks' attribute
+    ///
+    pub fn into_json_value(&self) -> serde_json::Value {
+        json!({
+            "name": self.name,
+            "policy": self.policy,
+            "state": self.state,
+            "start_time": self.start_time.to_rfc3339(),
+            "restart_count": self.restart_count,
+            "last_error": self.last_error,
+            "warning": self.warning,
+        })
+    }
+}
+
+/// Registry of the tasks spawned inside an instance
+///
+#[derive(Default)]
+pub struct TaskRegistry {
+    /// Tasks indexed by their id, names are not unique
+    ///
+    entries: Vec<TaskInfo>,
+}
+
+impl TaskRegistry {
+    /// Register a new running task and return its id
+    ///
+    pub fn register<N: Into<String>>(&mut self, name: N, policy: RestartPolicy) -> usize {
+        self.entries.push(TaskInfo {
+            name: name.into(),
+            policy,
+            state: TaskState::Running,
+            start_time: Utc::now(),
+            restart_count: 0,
+            consecutive_restarts: 0,
+            last_error: None,
+            warning: None,
+        });
+        self.entries.len() - 1
+    }
+
+    /// Update the task after its end, 'restarting' if the policy requests a restart
+    ///
+    pub fn end(&mut self, id: usize, result: &TaskResult, restarting: bool) {
+        if let Some(info) = self.entries.get_mut(id) {
+            if let Err(e) = result {
+                info.last_error = Some(format!("{:?}", e));
+            }
+            info.state = match (restarting, result) {
+                (true, _) => TaskState::Restarting,
+                (false, Ok(_)) => TaskState::Completed,
+                (false, Err(_)) => TaskState::Failed,
+            };
+        }
+    }
+
+    /// Delay to wait before the next restart of the task
+    ///
+    /// Return None when the task restarted too many times in a row and must be given up.
+    ///
+    pub fn next_restart_delay(&mut self, id: usize) -> Option<Duration> {
+        let info = self.entries.get_mut(id)?;
+        let run_time = (Utc::now() - info.start_time).to_std().unwrap_or_default();
+        if run_time >= TASK_RESTART_MAX_DELAY {
+            info.consecutive_restarts = 0;
+        }
+        if info.consecutive_restarts >= TASK_MAX_RESTARTS {
+            return None;
+        }
+        let delay = TASK_RESTART_BASE_DELAY.saturating_mul(1 << info.consecutive_restarts);
+        Some(delay.min(TASK_RESTART_MAX_DELAY))
+    }
+
+    /// The task starts again
+    ///
+    pub fn restart(&mut self, id: usize) {
+        if let Some(info) = self.entries.get_mut(id) {
+            info.state = TaskState::Running;
+            info.start_time = Utc::now();
+            info.restart_count += 1;
+            info.consecutive_restarts += 1;
+        }
+    }
+
+    /// The task reported a recoverable error
+    ///
+    pub fn set_warning<M: Into<String>>(&mut self, id: usize, message: M) {
+        if let Some(info) = self.entries.get_mut(id) {
+            info.warning = Some(message.into());
+        }
+    }
+
+    /// The condition reported by the task is over, return true if it had a warning
+    ///
+    pub fn clear_warning(&mut self, id: usize) -> bool {
+        self.entries
+            .get_mut(id)
+            .and_then(|info| info.warning.take())
+            .is_some()
+    }
+
+    /// Forget the warnings of all the tasks
+    ///
+    pub fn clear_all_warnings(&mut self) {
+        for info in self.entries.iter_mut() {
+            info.warning = None;
+        }
+    }
+
+    /// True if at least one task has an active warning
+    ///
+    pub fn has_warning(&self) -> bool {
+        self.entries.iter().any(|info| info.warning.is_some())
+    }
+
+    /// Mark all the living tasks as aborted
+    ///
+    pub fn abort_all(&mut self) {
+        for info in self.entries.iter_mut() {
+            if let TaskState::Running | TaskState::Restarting = info.state {
+                info.state = TaskState::Aborted;
+            }
+        }
+    }
+
+    /// Forget all the tasks
+    ///
+    pub fn clear(&mut self) {
+        self.entries.clear();
+    }
+
+    /// Information about all the registered tasks
+    ///
+    pub fn entries(&self) -> &Vec<TaskInfo> {
+        &self.entries
+    }
+
+    /// Json array of all the task information
+    ///
+    pub fn into_json_value(&self) -> serde_json::Value {
+        serde_json::Value::Array(self.entries.iter().map(|e| e.into_json_value()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        RestartPolicy, TaskRegistry, TaskState, TASK_MAX_RESTARTS, TASK_RESTART_BASE_DELAY,
+        TASK_RESTART_MAX_DELAY,
+    };
+    use crate::Error;
+
+    #[test]
+    fn test_restart_lifecycle() {
+        let mut registry = TaskRegistry::default();
+        let id = registry.register("loop", RestartPolicy::OnFailure);
+
+        let result = Err(Error::Timeout("query".to_string()));
+        let restart = RestartPolicy::OnFailure.must_restart(&result);
+        registry.end(id, &result, restart);
+        assert_eq!(registry.entries()[id].state, TaskState::Restarting);
+
+        registry.restart(id);
+        assert_eq!(registry.entries()[id].state, TaskState::Running);
+        assert_eq!(registry.entries()[id].restart_count, 1);
+
+        registry.abort_all();
+        assert_eq!(registry.entries()[id].state, TaskState::Aborted);
+    }
+
+    #[test]
+    fn test_warnings_per_task() {
+        let mut registry = TaskRegistry::default();
+        let a = registry.register("a", RestartPolicy::OnFailure);
+        let b = registry.register("b", RestartPolicy::OnFailure);
+
+        registry.set_warning(a, "timeout");
+        assert!(!registry.clear_warning(b));
+        assert!(registry.has_warning());
+        assert!(registry.clear_warning(a));
+        assert!(!registry.has_warning());
+    }
+
+    #[test]
+    fn test_restart_backoff() {
+        let mut registry = TaskRegistry::default();
+        let id = registry.register("loop", RestartPolicy::Always);
+
+        let mut delays = Vec::new();
+        while let Some(delay) = registry.next_restart_delay(id) {
+            delays.push(delay);
+            registry.restart(id);
+        }
+        assert_eq!(delays.len(), TASK_MAX_RESTARTS as usize);
+        assert_eq!(delays[0], TASK_RESTART_BASE_DELAY);
+        assert_eq!(delays[1], TASK_RESTART_BASE_DELAY * 2);
+        assert_eq!(*delays.last().unwrap(), TASK_RESTART_MAX_DELAY);
+    }
+}
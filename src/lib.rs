@@ -35,6 +35,8 @@ pub use error::Error;
 mod factory;
 pub use factory::production_order::InstanceSettings;
 pub use factory::production_order::ProductionOrder;
+pub use factory::scan_result::ScanReporter;
+pub use factory::scan_result::ScanResult;
 pub use factory::store::Product;
 pub use factory::store::Store;
 pub use factory::Factory;
@@ -45,7 +47,9 @@ pub mod instance;
 pub use instance::class::Class;
 pub use instance::class_builder::ClassBuilder;
 pub use instance::container::Container;
+pub use instance::element::{Element, SubElements};
 pub use instance::monitor::InstanceMonitor;
+pub use instance::task_registry::RestartPolicy;
 pub use instance::Instance;
 pub use instance::InstanceInner;
 
@@ -70,18 +74,22 @@ pub use traits::Scanner;
 //
 mod reactor;
 pub use reactor::message_dispatcher::MessageDispatcher;
+pub use reactor::recorder::{read_recording, MessageRecorder, RecordDirection, RecordEntry};
+pub use reactor::replayer::{MessageReplayer, ReplayMismatch, ReplayReport};
 pub use reactor::settings::ReactorSettings;
 pub use reactor::Reactor;
 
 // This module manage the message attributes (MQTT/TCP)
 // pub mod msg;
-pub type MessageClient = rumqttc::AsyncClient;
+pub use reactor::loopback::{LoopbackBroker, LoopbackClient, LoopbackMessage, LoopbackTransport};
+pub use reactor::transport::{MessageClient, MessageTransport, MqttTransport};
 
 //
 mod codec;
 pub use codec::boolean::BooleanCodec;
 pub use codec::eenum::EnumCodec;
 pub use codec::json::JsonCodec;
+pub use codec::memory_command::AccessSize;
 pub use codec::memory_command::MemoryCommandCodec;
 pub use codec::memory_command::MemoryCommandMode;
 pub use codec::number::NumberCodec;
@@ -101,19 +109,38 @@ pub type TaskResult = Result<(), Error>;
 
 //
 pub mod plugin;
+pub use plugin::handle::PluginHandle;
+pub use plugin::loader::PluginLoader;
 pub use plugin::Plugin;
+pub use plugin::PluginCapabilities;
 
 pub mod runtime;
+pub use runtime::structure::PlatformTree;
 pub use runtime::Runtime;
+pub use runtime::RuntimeHandle;
 
 pub mod env;
 
+pub mod tree;
+pub use tree::DeviceTree;
+pub use tree::TreeDiff;
+pub use tree::TreeEntry;
+
 pub use runtime::notification::attribute::AttributeMode;
 pub use runtime::notification::group::NotificationGroup;
+pub use runtime::notification::group::NotificationPull;
+pub use runtime::notification::group::NotificationQuery;
+pub use runtime::notification::group::SequencedNotification;
 pub use runtime::notification::AlertNotification;
+pub use runtime::notification::AlertSeverity;
+pub use runtime::notification::AlertStatus;
 pub use runtime::notification::AttributeNotification;
 pub use runtime::notification::ClassNotification;
 pub use runtime::notification::Notification;
+pub use runtime::notification::NotificationFilter;
+pub use runtime::notification::NotificationKind;
+pub use runtime::notification::NotificationSender;
+pub use runtime::notification::ScanNotification;
 pub use runtime::notification::StateNotification;
 
 /// Module that manage platform traces and logs
@@ -164,6 +191,8 @@ pub mod protocol;
 /// TODO => put in factory
 ///
 pub mod props;
+pub use props::typed::TypedSettings;
+pub use props::validation::ValidationReport;
 pub use props::Prop;
 pub use props::PropType;
 pub use props::Props;
@@ -1,5 +1,12 @@
+pub mod config;
+pub mod context;
+pub mod handle;
+pub mod loader;
 pub mod macro_helper;
-use std::ffi::{c_char, CStr};
+use std::ffi::{c_char, CStr, CString};
+
+use bitflags::bitflags;
+use serde::{Deserialize, Serialize};
 
 use crate::Store;
 
@@ -8,7 +15,77 @@ use crate::Store;
 /// Increment this number after a Plugin structure modification
 /// !!!!!
 ///
-static C_INTERFACE_VERSION: u32 = 0;
+/// The host must check it with 'plugin_interface_version' before calling
+/// 'plugin_entry_point', because the structure layout depends on it.
+///
+pub const C_INTERFACE_VERSION: u32 = 4;
+
+/// Error code when the production order cannot be parsed
+///
+pub const PRODUCE_ERR_INVALID_ORDER: u32 = 1;
+
+/// Error code when the driver reference is not managed by the plugin
+///
+pub const PRODUCE_ERR_UNKNOWN_DREF: u32 = 2;
+
+/// Error code when the runtime cannot accept the production order
+///
+pub const PRODUCE_ERR_RUNTIME: u32 = 3;
+
+/// Error code when the scan fails
+///
+pub const SCAN_ERR: u32 = 4;
+
+bitflags! {
+    /// Features provided by a plugin
+    ///
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct PluginCapabilities: u32 {
+        /// The plugin can scan the server to find instances
+        const SCAN = 1 << 0;
+        /// The plugin can produce instances
+        const PRODUCE = 1 << 1;
+        /// The plugin provides runtime notifications
+        const NOTIFICATIONS = 1 << 2;
+    }
+}
+
+/// Error returned as a json string through the C interface
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PluginCallError {
+    /// One of the *_ERR_* codes
+    ///
+    pub code: u32,
+
+    /// Human readable explanation
+    ///
+    pub message: String,
+}
+
+impl PluginCallError {
+    /// Create a new error
+    ///
+    pub fn new<M: Into<String>>(code: u32, message: M) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+
+    /// Serialize the error, the CString must be kept alive while the host reads it
+    ///
+    pub fn to_c_string(&self) -> CString {
+        let json_str = serde_json::to_string(self).unwrap_or_default();
+        CString::new(json_str).unwrap_or_default()
+    }
+}
+
+impl From<PluginCallError> for crate::Error {
+    fn from(error: PluginCallError) -> Self {
+        crate::Error::PluginError(format!("[{}] {}", error.code, error.message))
+    }
+}
 
 ///
 /// This structure provides the plugin interface
@@ -23,6 +100,11 @@ pub struct Plugin {
     ///
     pub c_interface_version: u32,
 
+    ///
+    /// Bits of 'PluginCapabilities'
+    ///
+    pub capabilities: u32,
+
     ///
     ///
     pub name: *const c_char,
@@ -47,31 +129,47 @@ pub struct Plugin {
     ///
     /// Return the list of all instances available on the server
     ///
+    /// Json array of scan results, or a json 'PluginCallError' object
+    ///
     pub scan: unsafe extern "C" fn() -> *const c_char,
 
     ///
     /// Produce a device matching the given json string configuration
     ///
-    pub produce: unsafe extern "C" fn(*const c_char) -> u32,
+    /// Return null on success, else a json 'PluginCallError' object
+    ///
+    pub produce: unsafe extern "C" fn(*const c_char) -> *const c_char,
 
     ///
-    /// Return the notifications
+    /// Return the notifications selected by a json 'NotificationQuery'
+    /// (null for all the available ones) as a json 'NotificationPull'
     ///
-    pub pull_notifications: unsafe extern "C" fn() -> *const c_char,
+    pub pull_notifications: unsafe extern "C" fn(*const c_char) -> *const c_char,
+
+    ///
+    /// Stop the plugin runtime and join its thread
+    ///
+    /// The library can be unloaded after, or started again with 'plugin_entry_point'
+    ///
+    pub stop: unsafe extern "C" fn(),
 }
 
 impl Plugin {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
         name: &'static CStr,
         version: &CStr,
+        capabilities: PluginCapabilities,
         join: unsafe extern "C" fn(),
         store: unsafe extern "C" fn() -> *const c_char,
         scan: unsafe extern "C" fn() -> *const c_char,
-        produce: unsafe extern "C" fn(*const c_char) -> u32,
-        pull_notifications: unsafe extern "C" fn() -> *const c_char,
+        produce: unsafe extern "C" fn(*const c_char) -> *const c_char,
+        pull_notifications: unsafe extern "C" fn(*const c_char) -> *const c_char,
+        stop: unsafe extern "C" fn(),
     ) -> Self {
         Plugin {
             c_interface_version: C_INTERFACE_VERSION,
+            capabilities: capabilities.bits(),
             name: name.as_ptr(),
             version: version.as_ptr(),
             join: join,
@@ -79,50 +177,14 @@ impl Plugin {
             scan: scan,
             produce: produce,
             pull_notifications: pull_notifications,
+            stop,
         }
     }
 
-    // /// Converts the ProductionOrder into a C string
-    // ///
-    // /// Don't forget "".as_c_str().as_ptr()" to use it with the DLL interfaces
-    // ///
-    // pub fn to_c_string(&self) -> Result<CString, crate::Error> {
-    //     let json_str =
-    //         serde_json::to_string(self).expect("Failed to serialize ProductionOrder to JSON");
-    //     CString::new(json_str)
-    //         .map_err(|e| crate::Error::InternalLogic(format!("Failed to build CString ({:?})", e)))
-    // }
-
     ///
     /// Converts a C-style string pointer into a `ProductionOrder`
     ///
     pub unsafe fn store_as_obj(&self) -> Result<Store, crate::Error> {
-        let c_str = (self.store)();
-
-        //
-        //
-        if c_str.is_null() {
-            return Err(crate::Error::InvalidArgument(
-                "Null C string pointer".to_string(),
-            ));
-        }
-
-        //
-        //
-        let c_str = unsafe { CStr::from_ptr(c_str) };
-        let str = c_str
-            .to_str()
-            .map_err(|e| crate::Error::InvalidArgument(format!("Invalid C string: {:?}", e)))?;
-
-        // println!("{:?}", str);
-
-        let json: serde_json::Value = serde_json::from_str(str)
-            .map_err(|e| crate::Error::InvalidArgument(format!("Invalid JSON: {:?}", e)))?;
-
-        let obj = serde_json::from_value(json).map_err(|e| {
-            crate::Error::InvalidArgument(format!("Failed to deserialize JSON: {:?}", e))
-        })?;
-
-        Ok(obj)
+        handle::parse_c_json((self.store)())
     }
 }
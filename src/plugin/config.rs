@@ -0,0 +1,134 @@
+use crate::{Error, ReactorSettings};
+use serde::{Deserialize, Serialize};
+use std::ffi::{c_char, CString};
+
+/// Options of a plugin, free json defined by each plugin
+///
+pub type PluginOptions = serde_json::Value;
+
+/// Log options given to the plugins when they start
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginLogOptions {
+    /// Also print logs on stdout
+    ///
+    pub enable_stdout: bool,
+
+    /// Enable debug logs
+    ///
+    pub debug: bool,
+
+    /// Enable trace logs
+    ///
+    pub trace: bool,
+}
+
+/// Configuration given by the host to 'plugin_entry_point' as a json string
+///
+/// Missing fields take their default value, so an empty object is a valid
+/// configuration.
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PluginConfig {
+    /// Broker and namespace used by the plugin runtime
+    ///
+    pub broker: ReactorSettings,
+
+    /// Logs of the plugin
+    ///
+    pub log: PluginLogOptions,
+
+    /// Options of each plugin, indexed by plugin name
+    ///
+    pub options: serde_json::Map<String, PluginOptions>,
+}
+
+impl PluginConfig {
+    /// Create a configuration with the default broker
+    ///
+    pub fn new(log: PluginLogOptions) -> Self {
+        Self {
+            log,
+            ..Default::default()
+        }
+    }
+
+    /// Set the broker and namespace
+    ///
+    pub fn with_broker(mut self, broker: ReactorSettings) -> Self {
+        self.broker = broker;
+        self
+    }
+
+    /// Set the options of the plugin 'name'
+    ///
+    pub fn with_plugin_options<N: Into<String>>(mut self, name: N, options: PluginOptions) -> Self {
+        self.options.insert(name.into(), options);
+        self
+    }
+
+    /// Options of the plugin 'name', null if the host did not provide any
+    ///
+    pub fn plugin_options(&self, name: &str) -> PluginOptions {
+        self.options
+            .get(name)
+            .cloned()
+            .unwrap_or(PluginOptions::Null)
+    }
+
+    /// Serialize the configuration for the C interface
+    ///
+    pub fn to_c_string(&self) -> Result<CString, Error> {
+        let json_str = serde_json::to_string(self)
+            .map_err(|e| Error::PluginError(format!("Cannot serialize plugin config: {}", e)))?;
+        CString::new(json_str)
+            .map_err(|e| Error::PluginError(format!("Invalid plugin config string: {}", e)))
+    }
+
+    /// Read the configuration given by the host, a null pointer gives the default one
+    ///
+    /// # Safety
+    ///
+    /// 'ptr' must be null or point to a nul terminated string that lives
+    /// during the call.
+    ///
+    pub unsafe fn from_c_str_ptr(ptr: *const c_char) -> Result<Self, Error> {
+        if ptr.is_null() {
+            return Ok(Self::default());
+        }
+        super::handle::parse_c_json(ptr)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PluginConfig, PluginLogOptions};
+    use crate::ReactorSettings;
+
+    #[test]
+    fn test_plugin_config() {
+        let config = PluginConfig::new(PluginLogOptions {
+            debug: true,
+            ..Default::default()
+        })
+        .with_broker(ReactorSettings::new(
+            "broker",
+            1884,
+            Some("lab".to_string()),
+        ))
+        .with_plugin_options("test", serde_json::json!({"speed": 3}));
+
+        let c_config = config.to_c_string().unwrap();
+        let read = unsafe { PluginConfig::from_c_str_ptr(c_config.as_ptr()) }.unwrap();
+        assert_eq!(read, config);
+        assert_eq!(read.broker.root_topic(), "lab/pza");
+        assert_eq!(read.plugin_options("test")["speed"], 3);
+        assert!(read.plugin_options("other").is_null());
+
+        let empty = unsafe { PluginConfig::from_c_str_ptr(c"{}".as_ptr()) }.unwrap();
+        assert_eq!(empty.broker, ReactorSettings::default());
+        assert!(!empty.log.debug);
+    }
+}
@@ -0,0 +1,442 @@
+use super::config::{PluginConfig, PluginOptions};
+use super::{
+    handle, Plugin, PluginCallError, PluginCapabilities, PRODUCE_ERR_INVALID_ORDER,
+    PRODUCE_ERR_RUNTIME, PRODUCE_ERR_UNKNOWN_DREF, SCAN_ERR,
+};
+use crate::runtime::notification::group::NotificationQuery;
+use crate::{
+    Factory, Logger, NotificationGroup, Producer, ProductionOrder, Reactor, Runtime, RuntimeHandle,
+    ScanMachine, Scanner, Store,
+};
+use std::ffi::{c_char, CStr, CString};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use tokio::sync::mpsc::Sender;
+
+/// Function of the plugin that provides its producers
+///
+pub type ProducersFn = fn() -> Vec<Box<dyn Producer>>;
+
+/// Function of the plugin that provides its scanners
+///
+pub type ScannersFn = fn() -> Vec<Box<dyn Scanner>>;
+
+/// C functions generated by 'plugin_interface!' for the 'Plugin' structure
+///
+pub struct PluginFunctions {
+    pub join: unsafe extern "C" fn(),
+    pub store: unsafe extern "C" fn() -> *const c_char,
+    pub scan: unsafe extern "C" fn() -> *const c_char,
+    pub produce: unsafe extern "C" fn(*const c_char) -> *const c_char,
+    pub pull_notifications: unsafe extern "C" fn(*const c_char) -> *const c_char,
+    pub stop: unsafe extern "C" fn(),
+}
+
+/// Everything a plugin needs between two calls of the host
+///
+/// Strings returned to the host are kept inside the context, they stay
+/// valid until the next call of the same function.
+///
+pub struct PluginContext {
+    /// Name of the plugin
+    ///
+    name: &'static CStr,
+
+    /// Logger of the plugin interface
+    ///
+    logger: Logger,
+
+    /// Configuration given by the host
+    ///
+    config: PluginConfig,
+
+    /// Factory, moved into the runtime when it starts
+    ///
+    factory: Option<Factory>,
+
+    /// Drivers of the plugin
+    ///
+    store: Store,
+
+    /// Drivers of the plugin as a json string
+    ///
+    store_c_string: CString,
+
+    /// Scanners of the plugin
+    ///
+    scan_machine: ScanMachine,
+
+    /// Last scan result as a json string
+    ///
+    scan_result: CString,
+
+    /// Last produce error as a json string
+    ///
+    produce_error: CString,
+
+    /// Notifications of the runtime
+    ///
+    notifications: Option<Arc<Mutex<NotificationGroup>>>,
+
+    /// Send production orders to the runtime
+    ///
+    production_order_sender: Option<Sender<ProductionOrder>>,
+
+    /// Stop the runtime
+    ///
+    runtime_handle: Option<RuntimeHandle>,
+
+    /// Thread that runs the runtime
+    ///
+    thread: Option<JoinHandle<()>>,
+}
+
+impl PluginContext {
+    /// Build the factory and the scanners of the plugin
+    ///
+    pub fn new(
+        name: &'static CStr,
+        config: PluginConfig,
+        producers: ProducersFn,
+        scanners: ScannersFn,
+    ) -> Self {
+        let mut logger = Logger::new_for_platform();
+        logger.set_plugin(name.to_string_lossy());
+
+        let mut scan_machine = ScanMachine::new();
+        scan_machine.add_scanners(scanners());
+
+        let mut factory = Factory::new();
+        factory.add_producers(producers());
+        let store = factory.store();
+        let store_c_string = factory.store_as_c_string().unwrap_or_default();
+
+        Self {
+            name,
+            logger,
+            config,
+            factory: Some(factory),
+            store,
+            store_c_string,
+            scan_machine,
+            scan_result: CString::default(),
+            produce_error: CString::default(),
+            notifications: None,
+            production_order_sender: None,
+            runtime_handle: None,
+            thread: None,
+        }
+    }
+
+    /// Start the runtime in its own thread, does nothing if already started
+    ///
+    pub fn start_runtime(&mut self) {
+        let factory = match self.factory.take() {
+            Some(factory) => factory,
+            None => return,
+        };
+
+        let reactor = Reactor::new(self.config.broker.clone());
+
+        let mut runtime = Runtime::new(factory, reactor);
+        runtime.set_plugin(self.name.to_string_lossy());
+
+        let notifications = runtime.clone_notifications();
+        self.scan_machine.set_notifications(notifications.clone());
+        self.notifications = Some(notifications);
+        self.production_order_sender = Some(runtime.clone_production_order_sender());
+        self.runtime_handle = Some(runtime.handle());
+
+        let logger = self.logger.clone();
+        self.thread = Some(std::thread::spawn(move || {
+            let tokio_runtime = match tokio::runtime::Builder::new_multi_thread()
+                .enable_all()
+                .build()
+            {
+                Ok(tokio_runtime) => tokio_runtime,
+                Err(e) => {
+                    logger.error(format!("Cannot build async runtime: {:?}", e));
+                    return;
+                }
+            };
+            if let Err(e) = tokio_runtime.block_on(runtime.task()) {
+                logger.error(format!("Runtime failure: {:?}", e));
+            }
+        }));
+    }
+
+    /// Drivers of the plugin as a json string
+    ///
+    pub fn store(&self) -> *const c_char {
+        self.store_c_string.as_ptr()
+    }
+
+    /// Scanners of the plugin, cloned to scan without holding the context
+    ///
+    pub fn scan_machine(&self) -> ScanMachine {
+        self.scan_machine.clone()
+    }
+
+    /// Keep the scan result, errors are given as a json 'PluginCallError'
+    ///
+    pub fn set_scan_result(&mut self, result: Result<CString, crate::Error>) -> *const c_char {
+        self.scan_result = match result {
+            Ok(result) => result,
+            Err(e) => PluginCallError::new(SCAN_ERR, format!("{:?}", e)).to_c_string(),
+        };
+        self.scan_result.as_ptr()
+    }
+
+    /// Send the production order to the runtime
+    ///
+    /// Return null on success, else a json 'PluginCallError'
+    ///
+    pub fn produce(&mut self, production_order: *const c_char) -> *const c_char {
+        let production_order = match ProductionOrder::from_c_str_ptr(production_order) {
+            Ok(po) => po,
+            Err(e) => {
+                return self.produce_error(
+                    PRODUCE_ERR_INVALID_ORDER,
+                    format!("Invalid production order: {:?}", e),
+                )
+            }
+        };
+
+        //
+        // Reject unknown references before sending them to the runtime
+        if !self.store.contains(production_order.dref()) {
+            let message = format!("Unknown driver reference: {}", production_order.dref());
+            return self.produce_error(PRODUCE_ERR_UNKNOWN_DREF, message);
+        }
+
+        let result = match &self.production_order_sender {
+            Some(sender) => sender
+                .try_send(production_order)
+                .map_err(|e| format!("Runtime cannot accept the order: {:?}", e)),
+            None => Err("Runtime is not started".to_string()),
+        };
+        match result {
+            Ok(_) => std::ptr::null(),
+            Err(message) => self.produce_error(PRODUCE_ERR_RUNTIME, message),
+        }
+    }
+
+    fn produce_error(&mut self, code: u32, message: String) -> *const c_char {
+        self.logger.error(message.clone());
+        self.produce_error = PluginCallError::new(code, message).to_c_string();
+        self.produce_error.as_ptr()
+    }
+
+    /// Notifications of the runtime selected by the json 'NotificationQuery'
+    ///
+    /// A null or invalid query selects all the available notifications.
+    ///
+    /// # Safety
+    ///
+    /// 'query' must be null or point to a nul terminated string that lives
+    /// during the call.
+    ///
+    pub unsafe fn pull_notifications(&self, query: *const c_char) -> *const c_char {
+        let query = if query.is_null() {
+            NotificationQuery::default()
+        } else {
+            handle::parse_c_json(query).unwrap_or_else(|e| {
+                self.logger
+                    .warn(format!("Invalid notification query: {:?}", e));
+                NotificationQuery::default()
+            })
+        };
+        match &self.notifications {
+            Some(notifications) => match notifications.lock() {
+                Ok(mut group) => group.pull_and_serialize(&query),
+                Err(_) => std::ptr::null(),
+            },
+            None => std::ptr::null(),
+        }
+    }
+
+    /// Request the runtime to stop and return its thread
+    ///
+    pub fn stop(&mut self) -> Option<JoinHandle<()>> {
+        if let Some(handle) = self.runtime_handle.take() {
+            self.logger.info("stop requested");
+            handle.shutdown();
+        }
+        self.production_order_sender = None;
+        self.thread.take()
+    }
+}
+
+/// Holds the context of a plugin, one per plugin library
+///
+/// All the C functions generated by 'plugin_interface!' go through it.
+///
+pub struct PluginContextSlot {
+    context: Mutex<Option<PluginContext>>,
+
+    /// Kept apart from the context to be readable while producers are built
+    ///
+    options: Mutex<PluginOptions>,
+}
+
+impl Default for PluginContextSlot {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PluginContextSlot {
+    /// Empty slot, usable in a static
+    ///
+    pub const fn new() -> Self {
+        Self {
+            context: Mutex::new(None),
+            options: Mutex::new(PluginOptions::Null),
+        }
+    }
+
+    /// A panic in an other call must not lock the plugin forever
+    ///
+    fn lock(&self) -> MutexGuard<'_, Option<PluginContext>> {
+        self.context.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Create the context and start the runtime
+    ///
+    /// 'config' is the json 'PluginConfig' given by the host, an invalid
+    /// configuration is logged and replaced by the default one.
+    /// Calling it again after 'stop' restarts the plugin with a new context.
+    ///
+    /// # Safety
+    ///
+    /// 'config' must be null or point to a nul terminated string that lives
+    /// during the call.
+    ///
+    pub unsafe fn entry_point(
+        &self,
+        name: &'static CStr,
+        version: &'static CStr,
+        producers: ProducersFn,
+        scanners: ScannersFn,
+        functions: PluginFunctions,
+        config: *const c_char,
+    ) -> Plugin {
+        let config_result = PluginConfig::from_c_str_ptr(config);
+        let config = config_result.clone().unwrap_or_default();
+
+        //
+        // Stop a previous runtime before replacing it, this also stops the log writer
+        self.stop();
+        crate::tracing::init(
+            config.log.enable_stdout,
+            false,
+            config.log.debug,
+            config.log.trace,
+        );
+
+        *self.options.lock().unwrap_or_else(|e| e.into_inner()) =
+            config.plugin_options(&name.to_string_lossy());
+
+        let mut context = PluginContext::new(name, config, producers, scanners);
+        context.logger.info("plugin_entry_point");
+        if let Err(e) = config_result {
+            context
+                .logger
+                .error(format!("Invalid plugin config, default used: {:?}", e));
+        }
+        context.start_runtime();
+        *self.lock() = Some(context);
+
+        Plugin::new(
+            name,
+            version,
+            PluginCapabilities::all(),
+            functions.join,
+            functions.store,
+            functions.scan,
+            functions.produce,
+            functions.pull_notifications,
+            functions.stop,
+        )
+    }
+
+    /// Options given by the host to this plugin, null if none
+    ///
+    pub fn options(&self) -> PluginOptions {
+        self.options
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// See 'PluginContext::store'
+    ///
+    pub fn store(&self) -> *const c_char {
+        match self.lock().as_ref() {
+            Some(context) => context.store(),
+            None => std::ptr::null(),
+        }
+    }
+
+    /// Scan the server
+    ///
+    /// The scan can be long, so the context is not locked during it and
+    /// the other calls (notifications, produce...) stay available.
+    ///
+    pub fn scan(&self) -> *const c_char {
+        let scan_machine = match self.lock().as_ref() {
+            Some(context) => context.scan_machine(),
+            None => return std::ptr::null(),
+        };
+        let result = scan_machine.scan_as_c_string();
+        match self.lock().as_mut() {
+            Some(context) => context.set_scan_result(result),
+            None => std::ptr::null(),
+        }
+    }
+
+    /// See 'PluginContext::produce'
+    ///
+    pub fn produce(&self, production_order: *const c_char) -> *const c_char {
+        match self.lock().as_mut() {
+            Some(context) => context.produce(production_order),
+            None => std::ptr::null(),
+        }
+    }
+
+    /// See 'PluginContext::pull_notifications'
+    ///
+    /// # Safety
+    ///
+    /// Same as 'PluginContext::pull_notifications'.
+    ///
+    pub unsafe fn pull_notifications(&self, query: *const c_char) -> *const c_char {
+        match self.lock().as_ref() {
+            Some(context) => context.pull_notifications(query),
+            None => std::ptr::null(),
+        }
+    }
+
+    /// Wait for the end of the runtime thread
+    ///
+    pub fn join(&self) {
+        //
+        // Do not keep the lock while waiting, the host may call 'stop'
+        let thread = self.lock().as_mut().and_then(|c| c.thread.take());
+        if let Some(thread) = thread {
+            let _ = thread.join();
+        }
+    }
+
+    /// Stop the runtime and wait for the end of its thread and of the log writer
+    ///
+    /// No thread runs the plugin code anymore, the library can be unloaded.
+    ///
+    pub fn stop(&self) {
+        let thread = self.lock().as_mut().and_then(|c| c.stop());
+        if let Some(thread) = thread {
+            let _ = thread.join();
+        }
+        crate::tracing::flush();
+    }
+}
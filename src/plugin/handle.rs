@@ -0,0 +1,228 @@
+use super::{Plugin, PluginCallError, PluginCapabilities, C_INTERFACE_VERSION};
+use crate::{Error, NotificationPull, NotificationQuery, ProductionOrder, ScanResult, Store};
+use serde::de::DeserializeOwned;
+use std::ffi::{c_char, CStr};
+
+/// Read a string provided by a plugin
+///
+/// # Safety
+///
+/// 'ptr' must be null or point to a nul terminated string that lives
+/// during the call.
+///
+pub unsafe fn read_c_str(ptr: *const c_char) -> Result<String, Error> {
+    if ptr.is_null() {
+        return Err(Error::PluginError("Null C string pointer".to_string()));
+    }
+    CStr::from_ptr(ptr)
+        .to_str()
+        .map(|s| s.to_string())
+        .map_err(|e| Error::PluginError(format!("Invalid C string: {:?}", e)))
+}
+
+/// Read a json string provided by a plugin and deserialize it
+///
+/// A json 'PluginCallError' object is converted into an error.
+///
+/// # Safety
+///
+/// Same as 'read_c_str'.
+///
+pub unsafe fn parse_c_json<T: DeserializeOwned>(ptr: *const c_char) -> Result<T, Error> {
+    let text = read_c_str(ptr)?;
+    let json: serde_json::Value = serde_json::from_str(&text)
+        .map_err(|e| Error::PluginError(format!("Invalid JSON: {:?}", e)))?;
+
+    if let Ok(call_error) = serde_json::from_value::<PluginCallError>(json.clone()) {
+        return Err(call_error.into());
+    }
+
+    serde_json::from_value(json)
+        .map_err(|e| Error::PluginError(format!("Failed to deserialize JSON: {:?}", e)))
+}
+
+/// Safe access to a plugin from the host side
+///
+/// Every call checks the pointers returned by the plugin, and converts the
+/// json errors of the plugin into 'Result's.
+///
+pub struct PluginHandle {
+    /// Interface provided by the plugin entry point
+    ///
+    plugin: Plugin,
+}
+
+impl PluginHandle {
+    /// Fails if the plugin does not use the same interface version as the host
+    ///
+    pub fn check_interface_version(version: u32) -> Result<(), Error> {
+        if version != C_INTERFACE_VERSION {
+            return Err(Error::PluginError(format!(
+                "Plugin interface version {} is not supported (host uses {})",
+                version, C_INTERFACE_VERSION
+            )));
+        }
+        Ok(())
+    }
+
+    /// Wrap the structure returned by 'plugin_entry_point'
+    ///
+    pub fn new(plugin: Plugin) -> Result<Self, Error> {
+        Self::check_interface_version(plugin.c_interface_version)?;
+        Ok(Self { plugin })
+    }
+
+    /// Name of the plugin
+    ///
+    pub fn name(&self) -> Result<String, Error> {
+        unsafe { read_c_str(self.plugin.name) }
+    }
+
+    /// Version of the plugin
+    ///
+    pub fn version(&self) -> Result<String, Error> {
+        unsafe { read_c_str(self.plugin.version) }
+    }
+
+    /// Features provided by the plugin
+    ///
+    pub fn capabilities(&self) -> PluginCapabilities {
+        PluginCapabilities::from_bits_truncate(self.plugin.capabilities)
+    }
+
+    /// Drivers that the plugin can produce
+    ///
+    pub fn store(&self) -> Result<Store, Error> {
+        unsafe { parse_c_json((self.plugin.store)()) }
+    }
+
+    /// Scan the server with the plugin scanners
+    ///
+    pub fn scan(&self) -> Result<Vec<ScanResult>, Error> {
+        self.require(PluginCapabilities::SCAN)?;
+        unsafe { parse_c_json((self.plugin.scan)()) }
+    }
+
+    /// Request the production of an instance
+    ///
+    pub fn produce(&self, production_order: &ProductionOrder) -> Result<(), Error> {
+        self.require(PluginCapabilities::PRODUCE)?;
+        let c_order = production_order.to_c_string()?;
+        let result = unsafe { (self.plugin.produce)(c_order.as_ptr()) };
+        if result.is_null() {
+            return Ok(());
+        }
+        let text = unsafe { read_c_str(result)? };
+        match serde_json::from_str::<PluginCallError>(&text) {
+            Ok(call_error) => Err(call_error.into()),
+            Err(e) => Err(Error::PluginError(format!("Invalid produce error {:?}", e))),
+        }
+    }
+
+    /// Get the notifications emitted by the plugin runtime that match the query
+    ///
+    pub fn pull_notifications(&self, query: &NotificationQuery) -> Result<NotificationPull, Error> {
+        self.require(PluginCapabilities::NOTIFICATIONS)?;
+        let json_str = serde_json::to_string(query)
+            .map_err(|e| Error::PluginError(format!("Invalid notification query: {:?}", e)))?;
+        let c_query = std::ffi::CString::new(json_str)
+            .map_err(|e| Error::PluginError(format!("Invalid notification query: {:?}", e)))?;
+        let ptr = unsafe { (self.plugin.pull_notifications)(c_query.as_ptr()) };
+        if ptr.is_null() {
+            return Ok(NotificationPull::default());
+        }
+        unsafe { parse_c_json(ptr) }
+    }
+
+    /// Wait for the end of the plugin thread
+    ///
+    pub fn join(&self) {
+        unsafe { (self.plugin.join)() }
+    }
+
+    /// Stop the plugin runtime and wait for the end of its thread
+    ///
+    pub fn stop(&self) {
+        unsafe { (self.plugin.stop)() }
+    }
+
+    fn require(&self, capability: PluginCapabilities) -> Result<(), Error> {
+        if !self.capabilities().contains(capability) {
+            return Err(Error::PluginError(format!(
+                "Plugin does not provide {:?}",
+                capability
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PluginHandle;
+    use crate::plugin::{Plugin, PluginCallError, PluginCapabilities, PRODUCE_ERR_UNKNOWN_DREF};
+    use crate::{Error, NotificationQuery, ProductionOrder};
+    use std::ffi::{c_char, CStr};
+
+    unsafe extern "C" fn join() {}
+
+    unsafe extern "C" fn stop() {}
+
+    unsafe extern "C" fn store() -> *const c_char {
+        c"{}".as_ptr()
+    }
+
+    unsafe extern "C" fn scan() -> *const c_char {
+        c"[{\"name\":\"psu\",\"dref\":\"fake.psu\",\"settings\":null,\"confidence\":0.5}]".as_ptr()
+    }
+
+    unsafe extern "C" fn produce(order: *const c_char) -> *const c_char {
+        let order = ProductionOrder::from_c_str_ptr(order).unwrap();
+        match order.dref.as_str() {
+            "fake.psu" => std::ptr::null(),
+            _ => {
+                let error = PluginCallError::new(PRODUCE_ERR_UNKNOWN_DREF, "unknown");
+                Box::leak(Box::new(error.to_c_string())).as_ptr()
+            }
+        }
+    }
+
+    unsafe extern "C" fn pull_notifications(_query: *const c_char) -> *const c_char {
+        std::ptr::null()
+    }
+
+    fn fake_plugin(name: &'static CStr) -> Plugin {
+        Plugin::new(
+            name,
+            c"v1",
+            PluginCapabilities::SCAN | PluginCapabilities::PRODUCE,
+            join,
+            store,
+            scan,
+            produce,
+            pull_notifications,
+            stop,
+        )
+    }
+
+    #[test]
+    fn test_plugin_handle() {
+        let handle = PluginHandle::new(fake_plugin(c"fake")).unwrap();
+        assert_eq!(handle.name().unwrap(), "fake");
+        assert_eq!(handle.scan().unwrap()[0].confidence, 0.5);
+        assert!(handle
+            .produce(&ProductionOrder::new("fake.psu", "psu"))
+            .is_ok());
+        assert!(matches!(
+            handle.produce(&ProductionOrder::new("other", "x")),
+            Err(Error::PluginError(_))
+        ));
+        assert!(handle
+            .pull_notifications(&NotificationQuery::default())
+            .is_err());
+
+        let mut old = fake_plugin(c"old");
+        old.c_interface_version = 0;
+        assert!(PluginHandle::new(old).is_err());
+    }
+}
@@ -0,0 +1,342 @@
+use super::config::PluginConfig;
+use super::handle::PluginHandle;
+use super::Plugin;
+use crate::factory::scan_result;
+use crate::{
+    Error, Logger, Notification, NotificationFilter, NotificationQuery, ProductionOrder,
+    ScanResult, Store,
+};
+use libloading::{Library, Symbol};
+use std::ffi::c_char;
+use std::path::{Path, PathBuf};
+
+/// Signature of the 'plugin_interface_version' symbol
+///
+type InterfaceVersionFn = unsafe extern "C" fn() -> u32;
+
+/// Signature of the 'plugin_entry_point' symbol, it takes a json 'PluginConfig'
+///
+type EntryPointFn = unsafe extern "C" fn(*const c_char) -> Plugin;
+
+/// A plugin loaded from a dynamic library
+///
+pub struct LoadedPlugin {
+    /// Safe interface to the plugin
+    ///
+    handle: PluginHandle,
+
+    /// Drivers provided by the plugin
+    ///
+    store: Store,
+
+    /// File of the library
+    ///
+    path: PathBuf,
+
+    /// Sequence of the last notification pulled from the plugin, one per filter
+    ///
+    /// A pull with a filter must not hide the skipped notifications from a pull
+    /// with an other filter.
+    ///
+    cursors: Vec<(NotificationFilter, u64)>,
+
+    /// Keep the library loaded while the handle is used (must be dropped last)
+    ///
+    _library: Library,
+}
+
+impl LoadedPlugin {
+    /// Safe interface to the plugin
+    ///
+    pub fn handle(&self) -> &PluginHandle {
+        &self.handle
+    }
+
+    /// Drivers provided by the plugin
+    ///
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    /// File of the library
+    ///
+    pub fn path(&self) -> &PathBuf {
+        &self.path
+    }
+}
+
+impl Drop for LoadedPlugin {
+    /// The plugin runtime must be over before the library is unloaded
+    ///
+    fn drop(&mut self) {
+        self.handle.stop();
+    }
+}
+
+/// Find, load and use the plugins of the platform
+///
+pub struct PluginLoader {
+    /// Logger of the loader
+    ///
+    logger: Logger,
+
+    /// Configuration given to the plugins
+    ///
+    config: PluginConfig,
+
+    /// Plugins successfully loaded
+    ///
+    plugins: Vec<LoadedPlugin>,
+
+    /// Drivers of all the plugins
+    ///
+    store: Store,
+}
+
+impl PluginLoader {
+    /// Create a loader without any plugin
+    ///
+    pub fn new(config: PluginConfig) -> Self {
+        Self {
+            logger: Logger::new_for_platform(),
+            config,
+            plugins: Vec::new(),
+            store: Store::default(),
+        }
+    }
+
+    /// Load the plugins of the system directories (see 'env::system_plugins_dir_paths')
+    ///
+    pub fn load_system_plugins(&mut self) -> Vec<Error> {
+        self.load_directories(&crate::env::system_plugins_dir_paths())
+    }
+
+    /// Load all the plugin libraries found in the directories
+    ///
+    /// A plugin that fails to load does not prevent the others to be loaded,
+    /// all the errors are returned.
+    ///
+    pub fn load_directories(&mut self, directories: &[PathBuf]) -> Vec<Error> {
+        let mut errors = Vec::new();
+        for directory in directories {
+            let entries = match std::fs::read_dir(directory) {
+                Ok(entries) => entries,
+                Err(e) => {
+                    self.logger
+                        .debug(format!("Skip plugin directory {:?}: {}", directory, e));
+                    continue;
+                }
+            };
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if !Self::is_plugin_library(&path) {
+                    continue;
+                }
+                if let Err(e) = self.load_file(&path) {
+                    self.logger
+                        .error(format!("Cannot load plugin {:?}: {:?}", path, e));
+                    errors.push(e);
+                }
+            }
+        }
+        errors
+    }
+
+    /// True if the file name looks like a dynamic library on this system
+    ///
+    fn is_plugin_library(path: &Path) -> bool {
+        let extension = crate::env::system_dyn_lib_extension().unwrap_or_default();
+        let prefix = crate::env::system_dyn_lib_prefix().unwrap_or_default();
+        let name = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or_default();
+        path.is_file()
+            && name.starts_with(&prefix)
+            && path.extension().and_then(|e| e.to_str()) == Some(extension.as_str())
+    }
+
+    /// Load a single plugin library
+    ///
+    pub fn load_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        let path = path.as_ref().to_path_buf();
+
+        //
+        // Opening the same file again returns the same library, its plugin is already running
+        if self.plugins.iter().any(|p| p.path == path) {
+            return Err(Error::PluginError(format!(
+                "Plugin {:?} is already loaded",
+                path
+            )));
+        }
+
+        //
+        // Loading a library runs its initialization code, plugins are trusted
+        let library = unsafe { Library::new(&path) }
+            .map_err(|e| Error::PluginError(format!("Cannot open library: {}", e)))?;
+
+        //
+        // Check the interface version before touching the plugin structure
+        let version = unsafe {
+            let version_fn: Symbol<InterfaceVersionFn> =
+                library.get(b"plugin_interface_version\0").map_err(|_| {
+                    Error::PluginError(
+                        "Missing 'plugin_interface_version', plugin is too old".to_string(),
+                    )
+                })?;
+            version_fn()
+        };
+        PluginHandle::check_interface_version(version)?;
+
+        let c_config = self.config.to_c_string()?;
+        let plugin = unsafe {
+            let entry_point: Symbol<EntryPointFn> = library
+                .get(b"plugin_entry_point\0")
+                .map_err(|e| Error::PluginError(format!("Missing 'plugin_entry_point': {}", e)))?;
+            entry_point(c_config.as_ptr())
+        };
+
+        //
+        // The plugin runtime is running, the library must never be closed under it
+        let handle = match PluginHandle::new(plugin) {
+            Ok(handle) => handle,
+            Err(e) => {
+                std::mem::forget(library);
+                return Err(e);
+            }
+        };
+
+        //
+        // From here, any error drops the plugin which stops it before closing the library
+        let mut loaded = LoadedPlugin {
+            handle,
+            store: Store::default(),
+            path,
+            cursors: Vec::new(),
+            _library: library,
+        };
+        loaded.store = loaded.handle.store()?;
+        let name = loaded.handle.name()?;
+
+        //
+        // Two plugins must not provide the same driver
+        for dref in loaded.store.products.keys() {
+            if self.store.contains(dref) {
+                return Err(Error::PluginError(format!(
+                    "Driver '{}' of plugin '{}' is already provided by an other plugin",
+                    dref, name
+                )));
+            }
+        }
+
+        self.logger
+            .info(format!("Plugin '{}' loaded from {:?}", name, loaded.path));
+        self.store.extend_by_copy(&loaded.store);
+        self.plugins.push(loaded);
+        Ok(())
+    }
+
+    /// Stop the plugin loaded from this file then unload its library
+    ///
+    pub fn unload<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Error> {
+        let path = path.as_ref();
+        let index = self
+            .plugins
+            .iter()
+            .position(|p| p.path == path)
+            .ok_or_else(|| Error::PluginError(format!("No plugin loaded from {:?}", path)))?;
+
+        //
+        // Dropping the plugin stops it before closing the library
+        self.plugins.remove(index);
+
+        self.store = Store::default();
+        for plugin in &self.plugins {
+            self.store.extend_by_copy(&plugin.store);
+        }
+        self.logger.info(format!("Plugin unloaded from {:?}", path));
+        Ok(())
+    }
+
+    /// Loaded plugins
+    ///
+    pub fn plugins(&self) -> &Vec<LoadedPlugin> {
+        &self.plugins
+    }
+
+    /// Drivers of all the plugins
+    ///
+    pub fn store(&self) -> &Store {
+        &self.store
+    }
+
+    /// Plugin that provides the driver
+    ///
+    pub fn plugin_for(&self, dref: &String) -> Option<&LoadedPlugin> {
+        self.plugins.iter().find(|p| p.store.contains(dref))
+    }
+
+    /// Send the production order to the plugin that owns the driver
+    ///
+    pub fn produce(&self, production_order: &ProductionOrder) -> Result<(), Error> {
+        match self.plugin_for(production_order.dref()) {
+            Some(plugin) => plugin.handle.produce(production_order),
+            None => Err(Error::BadSettings(format!(
+                "No plugin provides the driver '{}'",
+                production_order.dref()
+            ))),
+        }
+    }
+
+    /// Scan with all the plugins, results are deduplicated
+    ///
+    pub fn scan(&self) -> Vec<ScanResult> {
+        let mut results = Vec::new();
+        for plugin in &self.plugins {
+            match plugin.handle.scan() {
+                Ok(found) => results.extend(found),
+                Err(e) => self
+                    .logger
+                    .warn(format!("Scan failure on plugin {:?}: {:?}", plugin.path, e)),
+            }
+        }
+        scan_result::deduplicate(results)
+    }
+
+    /// Notifications of all the plugins since the previous pull with the same filter
+    ///
+    /// Notifications lost because a plugin buffer was full are reported in the logs.
+    ///
+    pub fn pull_notifications(&mut self, filter: &NotificationFilter) -> Vec<Notification> {
+        let mut notifications = Vec::new();
+        for plugin in &mut self.plugins {
+            let since = plugin
+                .cursors
+                .iter()
+                .find(|(f, _)| f == filter)
+                .map(|(_, sequence)| *sequence)
+                .unwrap_or(0);
+            let query = NotificationQuery::new(since).with_filter(filter.clone());
+            match plugin.handle.pull_notifications(&query) {
+                Ok(pulled) => {
+                    if pulled.dropped > 0 {
+                        self.logger.warn(format!(
+                            "{} notifications of plugin {:?} have been dropped",
+                            pulled.dropped, plugin.path
+                        ));
+                    }
+                    match plugin.cursors.iter_mut().find(|(f, _)| f == filter) {
+                        Some((_, sequence)) => *sequence = pulled.last_sequence,
+                        None => plugin.cursors.push((filter.clone(), pulled.last_sequence)),
+                    }
+                    notifications.extend(pulled.notifications.into_iter().map(|n| n.notification));
+                }
+                Err(e) => self.logger.debug(format!(
+                    "Cannot pull notifications of plugin {:?}: {:?}",
+                    plugin.path, e
+                )),
+            }
+        }
+        notifications
+    }
+}
@@ -1,8 +1,24 @@
+pub mod typed;
+pub mod validation;
+
 use serde::de::Error;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use serde_json::{Map, Value as JsonValue};
 use std::collections::HashMap;
 
+/// Json number of a prop value
+///
+/// Integral values are kept as integers, so they can be read with 'as_u64'
+/// or deserialized into integer fields (9600 and not 9600.0).
+///
+pub fn number_value(value: f64) -> JsonValue {
+    if value.fract() == 0.0 && value >= i64::MIN as f64 && value <= i64::MAX as f64 {
+        JsonValue::from(value as i64)
+    } else {
+        serde_json::json!(value)
+    }
+}
+
 #[derive(Default, Debug, Clone, Serialize, Deserialize)]
 ///
 /// Type of a Prop (match json types)
@@ -35,6 +51,41 @@ pub struct Prop {
     ///
     ///
     pub default: JsonValue,
+
+    /// True if the user must provide this setting
+    ///
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub required: bool,
+
+    /// Minimal value for a number
+    ///
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min: Option<f64>,
+
+    /// Maximal value for a number
+    ///
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max: Option<f64>,
+
+    /// Allowed values
+    ///
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub choices: Option<Vec<JsonValue>>,
+
+    /// Unit of a number (ex: "V", "Hz", "bauds")
+    ///
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+
+    /// Regex that a string must match (ex: for serial numbers)
+    ///
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+
+    /// Sub props of an object
+    ///
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub props: Option<Props>,
 }
 
 impl Prop {
@@ -46,6 +97,68 @@ impl Prop {
             description: description.into(),
             r#type: r#type,
             default: default,
+            ..Default::default()
+        }
+    }
+
+    /// The user must provide this setting
+    ///
+    pub fn with_required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+
+    /// Set the range of a number
+    ///
+    pub fn with_range(mut self, min: f64, max: f64) -> Self {
+        self.min = Some(min);
+        self.max = Some(max);
+        self
+    }
+
+    /// Set the allowed values
+    ///
+    pub fn with_choices<V: Into<JsonValue>>(mut self, choices: Vec<V>) -> Self {
+        self.choices = Some(choices.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Set the unit of a number
+    ///
+    pub fn with_unit<U: Into<String>>(mut self, unit: U) -> Self {
+        self.unit = Some(unit.into());
+        self
+    }
+
+    /// Set the regex that a string must match
+    ///
+    pub fn with_pattern<P: Into<String>>(mut self, pattern: P) -> Self {
+        self.pattern = Some(pattern.into());
+        self
+    }
+
+    /// Set the sub props of an object
+    ///
+    pub fn with_props(mut self, props: Props) -> Self {
+        self.props = Some(props);
+        self
+    }
+
+    /// True if the setting must be provided by the user
+    ///
+    pub fn is_required(&self) -> bool {
+        self.required
+    }
+
+    /// Value used when the setting is not provided
+    ///
+    /// Integral numbers are given as integers, even if the default has been
+    /// declared as a float.
+    ///
+    pub fn default_value(&self) -> JsonValue {
+        match (&self.r#type, self.default.as_f64()) {
+            (PropType::Number, Some(number)) if self.default.is_f64() => number_value(number),
+            _ => self.default.clone(),
         }
     }
 }
@@ -115,6 +228,24 @@ impl Props {
             .insert(name.into(), Prop::new(description, r#type, default));
     }
 
+    /// Add a prop built with the 'Prop' helpers
+    ///
+    pub fn add_prop<A: Into<String>>(&mut self, name: A, prop: Prop) {
+        self.entries.insert(name.into(), prop);
+    }
+
+    /// Get a prop from its name
+    ///
+    pub fn get(&self, name: &str) -> Option<&Prop> {
+        self.entries.get(name)
+    }
+
+    /// Iterate over props names and definitions
+    ///
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &Prop)> {
+        self.entries.iter()
+    }
+
     ///
     ///
     ///
@@ -145,7 +276,7 @@ impl Props {
             name,
             description,
             PropType::Number,
-            serde_json::json!(default.into()),
+            number_value(default.into()),
         );
     }
 
@@ -160,6 +291,79 @@ impl Props {
     ) {
         self.add_entry(name, description, PropType::Bool, JsonValue::Bool(default));
     }
+
+    /// Add a prop that accepts only some values (ex: baudrate choices)
+    ///
+    /// The type of the prop is deduced from the default value
+    ///
+    pub fn add_enum_prop<A: Into<String>, B: Into<String>, V: Into<JsonValue>>(
+        &mut self,
+        name: A,
+        description: B,
+        choices: Vec<V>,
+        default: V,
+    ) {
+        let default: JsonValue = default.into();
+        let r#type = match &default {
+            JsonValue::Bool(_) => PropType::Bool,
+            JsonValue::Number(_) => PropType::Number,
+            JsonValue::Array(_) => PropType::Array,
+            JsonValue::Object(_) => PropType::Object,
+            _ => PropType::String,
+        };
+        self.add_prop(
+            name,
+            Prop::new(description, r#type, default).with_choices(choices),
+        );
+    }
+
+    /// Add a number prop limited to [min, max]
+    ///
+    pub fn add_range_prop<A: Into<String>, B: Into<String>, U: Into<String>>(
+        &mut self,
+        name: A,
+        description: B,
+        min: f64,
+        max: f64,
+        unit: U,
+        default: f64,
+    ) {
+        self.add_prop(
+            name,
+            Prop::new(description, PropType::Number, number_value(default))
+                .with_range(min, max)
+                .with_unit(unit),
+        );
+    }
+
+    /// Add a string prop that must match a regex
+    ///
+    pub fn add_pattern_prop<A: Into<String>, B: Into<String>, P: Into<String>>(
+        &mut self,
+        name: A,
+        description: B,
+        pattern: P,
+        required: bool,
+    ) {
+        let mut prop =
+            Prop::new(description, PropType::String, JsonValue::Null).with_pattern(pattern);
+        prop.required = required;
+        self.add_prop(name, prop);
+    }
+
+    /// Add an object prop described by sub props
+    ///
+    pub fn add_object_prop<A: Into<String>, B: Into<String>>(
+        &mut self,
+        name: A,
+        description: B,
+        props: Props,
+    ) {
+        self.add_prop(
+            name,
+            Prop::new(description, PropType::Object, serde_json::json!({})).with_props(props),
+        );
+    }
 }
 
 impl From<Map<String, JsonValue>> for Props {
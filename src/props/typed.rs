@@ -0,0 +1,131 @@
+use super::Props;
+use crate::{Error, InstanceSettings};
+use serde::de::DeserializeOwned;
+
+/// Settings struct of a driver, extracted from the instance settings
+///
+/// 'props' and the struct fields must describe the same settings, use the
+/// 'typed_settings!' macro to generate both from a single definition.
+///
+pub trait TypedSettings: DeserializeOwned + Sized {
+    /// Props that describe the settings (used by 'Producer::props')
+    ///
+    fn props() -> Props;
+
+    /// Build the settings struct from the instance settings
+    ///
+    /// Props defaults are applied on missing settings, then the settings are
+    /// checked against the props before deserialization. Any problem produces
+    /// an 'Error::BadSettings' that contains the path of the faulty fields.
+    ///
+    fn from_settings(settings: &Option<InstanceSettings>) -> Result<Self, Error> {
+        let mut settings = settings.clone();
+        let report = Self::props().validate_settings("settings", &mut settings);
+        if !report.is_valid() {
+            return Err(Error::BadSettings(report.to_string()));
+        }
+
+        let value = settings.unwrap_or_else(|| serde_json::json!({}));
+        serde_path_to_error::deserialize(value)
+            .map_err(|e| Error::BadSettings(format!("'{}': {}", e.path(), e.inner())))
+    }
+}
+
+/// Define a settings struct and its props at once
+///
+/// Each field is followed by the 'Prop' that describes it. The struct must
+/// derive 'serde::Deserialize' (add it in the attributes).
+///
+/// ```ignore
+/// typed_settings! {
+///     #[derive(Debug, serde::Deserialize)]
+///     pub struct SerialSettings {
+///         pub port: String => Prop::new("serial port", PropType::String, json!(null)).with_required(),
+///         pub baudrate: u32 => Prop::new("serial baudrate", PropType::Number, json!(9600)),
+///     }
+/// }
+/// ```
+///
+#[macro_export]
+macro_rules! typed_settings {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $(
+                $(#[$field_meta:meta])*
+                $field_vis:vis $field:ident : $type:ty => $prop:expr
+            ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $(
+                $(#[$field_meta])*
+                $field_vis $field: $type,
+            )*
+        }
+
+        impl $crate::TypedSettings for $name {
+            fn props() -> $crate::Props {
+                let mut props = $crate::Props::default();
+                $(
+                    props.add_prop(stringify!($field), $prop);
+                )*
+                props
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TypedSettings;
+    use crate::{Error, Prop, PropType};
+    use serde_json::json;
+
+    crate::typed_settings! {
+        #[derive(Debug, serde::Deserialize)]
+        struct SerialSettings {
+            port: String => Prop::new("serial port", PropType::String, json!(null)).with_required(),
+            baudrate: u32 => Prop::new("serial baudrate", PropType::Number, json!(9600.0))
+                .with_choices(vec![9600, 115200]),
+        }
+    }
+
+    crate::typed_settings! {
+        #[derive(Debug, serde::Deserialize)]
+        struct TimeoutSettings {
+            timeout: u16 => Prop::new("read timeout", PropType::Number, json!(null)),
+        }
+    }
+
+    #[test]
+    fn test_typed_settings() {
+        let props = SerialSettings::props();
+        assert!(props.get("port").unwrap().is_required());
+        assert!(props.get("baudrate").is_some());
+
+        let settings = SerialSettings::from_settings(&Some(json!({ "port": "/dev/ttyUSB0" })))
+            .expect("settings must be valid");
+        assert_eq!(settings.port, "/dev/ttyUSB0");
+        assert_eq!(settings.baudrate, 9600);
+
+        match SerialSettings::from_settings(&Some(json!({ "baudrate": 4800 }))) {
+            Err(Error::BadSettings(message)) => {
+                assert!(message.contains("'port'"));
+                assert!(message.contains("'baudrate'"));
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_typed_settings_error_path() {
+        let settings = TimeoutSettings::from_settings(&Some(json!({ "timeout": 10 }))).unwrap();
+        assert_eq!(settings.timeout, 10);
+        match TimeoutSettings::from_settings(&Some(json!({ "timeout": 100000 }))) {
+            Err(Error::BadSettings(message)) => assert!(message.starts_with("'timeout'")),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+}
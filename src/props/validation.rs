@@ -0,0 +1,425 @@
+use super::{Prop, PropType, Props};
+use crate::InstanceSettings;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use std::fmt::Display;
+
+/// Kind of problem found in instance settings
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum ValidationIssueKind {
+    /// The driver reference is not managed by the factory
+    ///
+    UnknownDriverRef,
+
+    /// Settings must be a json object
+    ///
+    NotAnObject,
+
+    /// A required setting is not provided
+    ///
+    MissingRequired,
+
+    /// The setting does not match the prop type
+    ///
+    WrongType,
+
+    /// The number is not inside [min, max]
+    ///
+    OutOfRange,
+
+    /// The value is not among the prop choices
+    ///
+    NotInChoices,
+
+    /// The string does not match the prop pattern
+    ///
+    PatternMismatch,
+
+    /// The setting is not declared in the props (does not block the production)
+    ///
+    UnknownKey,
+
+    /// The prop itself is wrong (ex: invalid regex), the driver must be fixed
+    ///
+    InvalidProp,
+}
+
+/// A single problem found in instance settings
+///
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationIssue {
+    /// Name of the instance
+    ///
+    pub instance: String,
+
+    /// Setting key if the problem is related to a setting
+    ///
+    pub key: Option<String>,
+
+    /// Kind of problem
+    ///
+    pub kind: ValidationIssueKind,
+
+    /// Human readable explanation
+    ///
+    pub message: String,
+}
+
+impl ValidationIssue {
+    /// True if the instance cannot be produced because of this issue
+    ///
+    pub fn is_blocking(&self) -> bool {
+        self.kind != ValidationIssueKind::UnknownKey
+    }
+}
+
+impl Display for ValidationIssue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.key {
+            Some(key) => write!(f, "[{}] '{}': {}", self.instance, key, self.message),
+            None => write!(f, "[{}] {}", self.instance, self.message),
+        }
+    }
+}
+
+/// Report that lists every problem found in production orders
+///
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationReport {
+    /// Problems found
+    ///
+    pub issues: Vec<ValidationIssue>,
+}
+
+impl ValidationReport {
+    /// Append a new issue
+    ///
+    pub fn push<I: Into<String>, M: Into<String>>(
+        &mut self,
+        instance: I,
+        key: Option<String>,
+        kind: ValidationIssueKind,
+        message: M,
+    ) {
+        self.issues.push(ValidationIssue {
+            instance: instance.into(),
+            key,
+            kind,
+            message: message.into(),
+        });
+    }
+
+    /// Append issues of an other report
+    ///
+    pub fn extend(&mut self, other: ValidationReport) {
+        self.issues.extend(other.issues);
+    }
+
+    /// True if no issue blocks the production
+    ///
+    pub fn is_valid(&self) -> bool {
+        !self.issues.iter().any(|i| i.is_blocking())
+    }
+
+    /// True if there is no issue at all
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+impl Display for ValidationReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for issue in &self.issues {
+            writeln!(f, "{}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+impl Props {
+    /// Check settings against the props and fill missing settings with defaults
+    ///
+    pub fn validate_settings(
+        &self,
+        instance: &str,
+        settings: &mut Option<InstanceSettings>,
+    ) -> ValidationReport {
+        let mut report = ValidationReport::default();
+
+        //
+        // No settings is like empty settings
+        if settings.is_none() {
+            if self.entries.is_empty() {
+                return report;
+            }
+            *settings = Some(json!({}));
+        }
+
+        if let Some(value) = settings.as_mut() {
+            self.validate_object(instance, None, value, &mut report);
+        }
+        report
+    }
+
+    /// Check a json object against the props, 'path' is the key of the object
+    /// inside the parent settings (None for the root)
+    ///
+    fn validate_object(
+        &self,
+        instance: &str,
+        path: Option<&str>,
+        value: &mut JsonValue,
+        report: &mut ValidationReport,
+    ) {
+        let obj = match value.as_object_mut() {
+            Some(obj) => obj,
+            None => {
+                report.push(
+                    instance,
+                    path.map(|p| p.to_string()),
+                    ValidationIssueKind::NotAnObject,
+                    "settings must be a json object",
+                );
+                return;
+            }
+        };
+
+        //
+        // Check declared props
+        for (key, prop) in &self.entries {
+            let key_path = match path {
+                Some(p) => format!("{}.{}", p, key),
+                None => key.clone(),
+            };
+            match obj.get_mut(key) {
+                None | Some(JsonValue::Null) => {
+                    if prop.is_required() {
+                        report.push(
+                            instance,
+                            Some(key_path),
+                            ValidationIssueKind::MissingRequired,
+                            format!("required setting of type {:?} is missing", prop.r#type),
+                        );
+                    } else if !prop.default.is_null() {
+                        //
+                        // A defaulted object must still provide its required sub props
+                        let mut default = prop.default_value();
+                        if prop.props.is_some() {
+                            prop.validate_value(instance, &key_path, &mut default, report);
+                        }
+                        obj.insert(key.clone(), default);
+                    }
+                }
+                Some(value) => prop.validate_value(instance, &key_path, value, report),
+            }
+        }
+
+        //
+        // Keys that the driver will not use
+        for key in obj.keys() {
+            if !self.entries.contains_key(key) {
+                report.push(
+                    instance,
+                    Some(match path {
+                        Some(p) => format!("{}.{}", p, key),
+                        None => key.clone(),
+                    }),
+                    ValidationIssueKind::UnknownKey,
+                    "setting not declared by the driver",
+                );
+            }
+        }
+    }
+}
+
+impl Prop {
+    /// Check a provided value against this prop
+    ///
+    fn validate_value(
+        &self,
+        instance: &str,
+        key_path: &str,
+        value: &mut JsonValue,
+        report: &mut ValidationReport,
+    ) {
+        if !self.r#type.matches(value) {
+            report.push(
+                instance,
+                Some(key_path.to_string()),
+                ValidationIssueKind::WrongType,
+                format!("expected {:?} but got {}", self.r#type, value),
+            );
+            return;
+        }
+
+        if let Some(number) = value.as_f64() {
+            let below = self.min.map(|min| number < min).unwrap_or(false);
+            let above = self.max.map(|max| number > max).unwrap_or(false);
+            if below || above {
+                report.push(
+                    instance,
+                    Some(key_path.to_string()),
+                    ValidationIssueKind::OutOfRange,
+                    format!(
+                        "{} is not inside [{:?}, {:?}]{}",
+                        number,
+                        self.min,
+                        self.max,
+                        self.unit
+                            .as_ref()
+                            .map(|u| format!(" {}", u))
+                            .unwrap_or_default()
+                    ),
+                );
+            }
+        }
+
+        if let Some(choices) = &self.choices {
+            if !choices.iter().any(|c| json_eq(c, value)) {
+                report.push(
+                    instance,
+                    Some(key_path.to_string()),
+                    ValidationIssueKind::NotInChoices,
+                    format!("{} is not in {:?}", value, choices),
+                );
+            }
+        }
+
+        if let (Some(pattern), Some(text)) = (&self.pattern, value.as_str()) {
+            match Regex::new(pattern) {
+                Ok(re) if re.is_match(text) => {}
+                Ok(_) => report.push(
+                    instance,
+                    Some(key_path.to_string()),
+                    ValidationIssueKind::PatternMismatch,
+                    format!("{:?} does not match {:?}", text, pattern),
+                ),
+                Err(e) => report.push(
+                    instance,
+                    Some(key_path.to_string()),
+                    ValidationIssueKind::InvalidProp,
+                    format!("invalid pattern {:?} ({})", pattern, e),
+                ),
+            }
+        }
+
+        if let Some(props) = &self.props {
+            props.validate_object(instance, Some(key_path), value, report);
+        }
+    }
+}
+
+/// Compare json values, numbers are compared as f64 (9600 == 9600.0)
+///
+fn json_eq(a: &JsonValue, b: &JsonValue) -> bool {
+    match (a.as_f64(), b.as_f64()) {
+        (Some(x), Some(y)) => x == y,
+        _ => a == b,
+    }
+}
+
+impl PropType {
+    /// True if the json value matches this type
+    ///
+    pub fn matches(&self, value: &JsonValue) -> bool {
+        match self {
+            PropType::Bool => value.is_boolean(),
+            PropType::Number => value.is_number(),
+            PropType::String => value.is_string(),
+            PropType::Array => value.is_array(),
+            PropType::Object => value.is_object(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ValidationIssueKind;
+    use crate::{Prop, PropType, Props};
+    use serde_json::json;
+
+    #[test]
+    fn test_validate_settings() {
+        let mut props = Props::default();
+        props.add_number_prop("timeout", "read timeout", 2);
+        props.add_prop(
+            "port",
+            Prop::new("serial port", PropType::String, json!(null)).with_required(),
+        );
+        props.add_bool_prop("verbose", "more logs", false);
+
+        let mut settings = Some(json!({ "verbose": "yes", "extra": 1 }));
+        let report = props.validate_settings("dev", &mut settings);
+
+        let kinds: Vec<ValidationIssueKind> =
+            report.issues.iter().map(|i| i.kind.clone()).collect();
+        assert!(kinds.contains(&ValidationIssueKind::MissingRequired));
+        assert!(kinds.contains(&ValidationIssueKind::WrongType));
+        assert!(kinds.contains(&ValidationIssueKind::UnknownKey));
+        assert!(!report.is_valid());
+        let settings = settings.unwrap();
+        assert_eq!(settings["timeout"], json!(2));
+        assert_eq!(settings["timeout"].as_u64(), Some(2));
+    }
+
+    #[test]
+    fn test_validate_rich_props() {
+        let mut usb = Props::default();
+        usb.add_pattern_prop("usb_serial", "serial number", "^[0-9A-F]{8}$", true);
+
+        let mut props = Props::default();
+        props.add_enum_prop("baudrate", "serial baudrate", vec![9600, 115200], 9600);
+        props.add_range_prop("voltage", "output voltage", 0.0, 30.0, "V", 5.0);
+        props.add_object_prop("usb", "usb identification", usb);
+
+        let mut settings = Some(json!({
+            "baudrate": 115200,
+            "voltage": 42,
+            "usb": { "usb_serial": "xyz" }
+        }));
+        let report = props.validate_settings("dev", &mut settings);
+
+        let keys: Vec<(Option<String>, ValidationIssueKind)> = report
+            .issues
+            .iter()
+            .map(|i| (i.key.clone(), i.kind.clone()))
+            .collect();
+        assert_eq!(keys.len(), 2);
+        assert!(keys.contains(&(Some("voltage".to_string()), ValidationIssueKind::OutOfRange)));
+        assert!(keys.contains(&(
+            Some("usb.usb_serial".to_string()),
+            ValidationIssueKind::PatternMismatch
+        )));
+    }
+
+    #[test]
+    fn test_validate_defaulted_props() {
+        let mut usb = Props::default();
+        usb.add_pattern_prop("usb_serial", "serial number", "^[0-9A-F]{8}$", true);
+
+        let mut props = Props::default();
+        props.add_range_prop("voltage", "output voltage", 0.0, 30.0, "V", 5.0);
+        props.add_object_prop("usb", "usb identification", usb);
+        props.add_pattern_prop("name", "instance name", "[unclosed", false);
+
+        //
+        // The defaulted 'usb' object misses its required serial
+        let mut settings = Some(json!({ "name": "psu" }));
+        let report = props.validate_settings("dev", &mut settings);
+        let keys: Vec<(Option<String>, ValidationIssueKind)> = report
+            .issues
+            .iter()
+            .map(|i| (i.key.clone(), i.kind.clone()))
+            .collect();
+        assert_eq!(keys.len(), 2);
+        assert!(keys.contains(&(
+            Some("usb.usb_serial".to_string()),
+            ValidationIssueKind::MissingRequired
+        )));
+        assert!(keys.contains(&(Some("name".to_string()), ValidationIssueKind::InvalidProp)));
+        assert_eq!(settings.unwrap()["voltage"], json!(5));
+    }
+}
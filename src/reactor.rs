@@ -4,22 +4,52 @@ use bytes::Bytes;
 use futures::FutureExt;
 pub use settings::ReactorSettings;
 mod message_engine;
-use message_engine::MessageEngine;
-use tokio::sync::mpsc::Sender;
+use message_engine::{dispatch_message, MessageEngine};
+pub mod loopback;
 pub mod message_dispatcher;
+pub mod recorder;
+pub mod replayer;
+pub mod transport;
+use crate::instance::State;
 use crate::{AttributeBuilder, Error, MessageDispatcher, MessageHandler, TaskResult, TaskSender};
-use crate::{MessageClient, Notification};
+use crate::{DriverOperations, Instance, InstanceMonitor, ProductionOrder};
+use crate::{Logger, MessageClient, NotificationSender};
 use chrono::prelude::*;
+use loopback::LoopbackBroker;
 use rand::distributions::Alphanumeric;
 use rand::Rng;
+use recorder::MessageRecorder;
 use rumqttc::AsyncClient;
 use rumqttc::{MqttOptions, QoS};
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::Mutex;
 
+/// Time given to a driver to be mounted by 'start_loopback_instance'
+///
+pub static LOOPBACK_MOUNT_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Publish, retained on '{topic}/enabled', the enablement of a class or an attribute
+///
+pub async fn publish_enablement(
+    message_client: &MessageClient,
+    topic: &str,
+    enabled: bool,
+) -> Result<(), Error> {
+    message_client
+        .publish(
+            format!("{}/enabled", topic),
+            QoS::AtLeastOnce,
+            true,
+            enabled.to_string(),
+        )
+        .await
+}
+
 struct PzaScanMessageHandler {
     message_client: MessageClient,
+    /// Root topic on which the scan answer is published
+    topic: String,
 }
 
 #[async_trait]
@@ -30,18 +60,12 @@ impl MessageHandler for PzaScanMessageHandler {
 
         self.message_client
             .publish(
-                format!("pza"),
+                self.topic.clone(),
                 QoS::AtLeastOnce,
                 false,
                 format!("{}", now.timestamp_millis()),
             )
             .await
-            .map_err(|e| Error::PublishError {
-                topic: "pza".to_string(),
-                pyl_size: now.timestamp_millis().to_string().len(),
-                cause: e.to_string(),
-            })?;
-        Ok(())
     }
 }
 
@@ -53,6 +77,9 @@ impl MessageHandler for PzaScanMessageHandler {
 pub struct Reactor {
     is_started: bool,
 
+    /// Broker and namespace
+    settings: ReactorSettings,
+
     /// Root topic (namespace/pza)
     root_topic: String,
 
@@ -63,6 +90,12 @@ pub struct Reactor {
     message_dispatcher: Arc<Mutex<MessageDispatcher>>,
 
     scan_handler: Option<Arc<Mutex<PzaScanMessageHandler>>>,
+
+    /// Records the attributes traffic if enabled in the settings
+    recorder: Option<MessageRecorder>,
+
+    /// Logger of the message management
+    logger: Logger,
 }
 
 impl Reactor {
@@ -72,7 +105,7 @@ impl Reactor {
     ///
     /// * `core` - The core of the reactor
     ///
-    pub fn new(_settings: ReactorSettings) -> Self {
+    pub fn new(settings: ReactorSettings) -> Self {
         // let data = ;
 
         // Server hostname
@@ -80,10 +113,13 @@ impl Reactor {
 
         Reactor {
             is_started: false,
-            root_topic: format!("pza"),
+            root_topic: settings.root_topic(),
+            settings,
             message_client: None,
             message_dispatcher: Arc::new(Mutex::new(MessageDispatcher::new())),
             scan_handler: None,
+            recorder: None,
+            logger: Logger::new_for_reactor(),
         }
     }
 
@@ -109,30 +145,41 @@ impl Reactor {
 
         let mut mqttoptions = MqttOptions::new(
             format!("rumqtt-sync-{}", Self::generate_random_string(5)),
-            "localhost",
-            1883,
+            self.settings.addr.clone(),
+            self.settings.port_mqtt,
         );
         mqttoptions.set_keep_alive(Duration::from_secs(3));
 
         let (client, event_loop) = AsyncClient::new(mqttoptions, 100);
 
-        self.message_client = Some(client.clone());
+        self.message_client = Some(client.clone().into());
+        self.recorder = self.create_recorder()?;
 
         self.scan_handler = Some(Arc::new(Mutex::new(PzaScanMessageHandler {
-            message_client: client.clone(),
+            message_client: client.clone().into(),
+            topic: self.root_topic.clone(),
         })));
 
         let h = self.scan_handler.as_ref().unwrap().clone();
+        let scan_topic = self.root_topic.clone();
         let dispatcher = self.message_dispatcher.clone();
-        let mut message_engine = MessageEngine::new(self.message_dispatcher.clone(), event_loop);
+        let mut message_engine = MessageEngine::new(
+            self.message_dispatcher.clone(),
+            event_loop,
+            self.recorder.clone(),
+            self.logger.clone(),
+        );
         main_task_sender.spawn_with_name(
             "REACTOR CORE",
             async move {
                 dispatcher
                     .lock()
                     .await
-                    .register_message_attribute("pza".to_string(), h);
-                client.subscribe("pza", QoS::AtLeastOnce).await.unwrap();
+                    .register_message_attribute(scan_topic.clone(), h);
+                client
+                    .subscribe(scan_topic, QoS::AtLeastOnce)
+                    .await
+                    .unwrap();
                 message_engine.run().await;
                 println!("!!!!!!!!!!!! ReactorCore STOP not runiing !!!!!!!!!!!!!!!!!!!!!!");
                 Ok(())
@@ -144,16 +191,140 @@ impl Reactor {
         Ok(())
     }
 
+    /// Start without broker, attributes publish through 'message_client'
+    ///
+    /// Commands must be injected through the message dispatcher (ex: replay).
+    ///
+    pub fn start_with_client(&mut self, message_client: MessageClient) -> Result<(), Error> {
+        self.message_client = Some(message_client);
+        self.recorder = self.create_recorder()?;
+        self.is_started = true;
+        Ok(())
+    }
+
+    /// Start on an in-memory broker, to run attributes and drivers without MQTT broker
+    ///
+    pub fn start_loopback(&mut self, broker: &LoopbackBroker) -> Result<(), Error> {
+        let (transport, mut messages) = broker.connect();
+        self.start_with_client(MessageClient::new(transport))?;
+
+        let dispatcher = self.message_dispatcher.clone();
+        let recorder = self.recorder.clone();
+        let logger = self.logger.clone();
+        tokio::spawn(async move {
+            while let Some(message) = messages.recv().await {
+                dispatch_message(
+                    &dispatcher,
+                    &recorder,
+                    &logger,
+                    &message.topic,
+                    &message.payload,
+                )
+                .await;
+            }
+        });
+        Ok(())
+    }
+
+    /// Start on an in-memory broker and mount 'driver' as the instance of 'production_order'
+    ///
+    /// Harness to test a driver without broker, the monitor and the state machine of
+    /// the instance are spawned. The instance is returned once the driver is mounted.
+    ///
+    pub async fn start_loopback_instance(
+        mut self,
+        broker: &LoopbackBroker,
+        production_order: ProductionOrder,
+        driver: Box<dyn DriverOperations>,
+    ) -> Result<Instance, Error> {
+        self.start_loopback(broker)?;
+        let (mut monitor, instance) = InstanceMonitor::new(self, None, driver, production_order);
+        tokio::spawn(async move { monitor.run().await });
+        let mut fsm = instance.clone();
+        tokio::spawn(async move { fsm.run_fsm().await });
+
+        tokio::time::timeout(LOOPBACK_MOUNT_TIMEOUT, async {
+            while !matches!(instance.state().await, State::Running) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .map_err(|_| {
+            Error::Timeout(format!(
+                "instance '{}' not running after {:?}",
+                instance.name(),
+                LOOPBACK_MOUNT_TIMEOUT
+            ))
+        })?;
+        Ok(instance)
+    }
+
+    /// Recorder requested by the settings
+    ///
+    fn create_recorder(&self) -> Result<Option<MessageRecorder>, Error> {
+        self.settings
+            .record_path
+            .as_ref()
+            .map(MessageRecorder::create)
+            .transpose()
+    }
+
+    /// Recorder of the attributes traffic, if enabled in the settings
+    ///
+    pub fn recorder(&self) -> Option<MessageRecorder> {
+        self.recorder.clone()
+    }
+
+    /// Dispatcher of the incoming messages to the attributes
+    ///
+    pub fn message_dispatcher(&self) -> Arc<Mutex<MessageDispatcher>> {
+        self.message_dispatcher.clone()
+    }
+
+    /// Give the messages received on 'topic' to 'handler'
+    ///
+    /// The dispatcher only keeps a weak reference, the caller must keep the handler alive.
+    ///
+    pub async fn subscribe_handler<S: Into<String>>(
+        &self,
+        topic: S,
+        handler: Arc<Mutex<dyn MessageHandler>>,
+    ) -> Result<(), Error> {
+        let topic = topic.into();
+        let message_client = self
+            .message_client
+            .as_ref()
+            .ok_or(Error::InternalLogic("reactor is not started".to_string()))?;
+        self.message_dispatcher
+            .lock()
+            .await
+            .register_message_attribute(topic.clone(), handler);
+        message_client.subscribe(topic, QoS::AtLeastOnce).await
+    }
+
+    /// Publish the enablement of a class or an attribute
+    ///
+    pub async fn publish_enablement(&self, topic: &str, enabled: bool) -> Result<(), Error> {
+        match &self.message_client {
+            Some(message_client) => publish_enablement(message_client, topic, enabled).await,
+            None => Err(Error::InternalLogic("reactor is not started".to_string())),
+        }
+    }
+
     pub fn create_new_attribute(
         &self,
         // device_dyn_info: Option<ThreadSafeInfoDynamicDeviceStatus>,
-        r_notifier: Option<Sender<Notification>>,
+        r_notifier: Option<NotificationSender>,
     ) -> AttributeBuilder {
-        AttributeBuilder::new(
+        let builder = AttributeBuilder::new(
             None,
             self.message_client.as_ref().unwrap().clone(),
             Arc::downgrade(&self.message_dispatcher),
             r_notifier,
-        )
+        );
+        match &self.recorder {
+            Some(recorder) => builder.with_recorder(recorder.clone()),
+            None => builder,
+        }
     }
 }
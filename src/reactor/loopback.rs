@@ -0,0 +1,308 @@
+use super::transport::{MessageClient, MessageTransport};
+use crate::Error;
+use async_trait::async_trait;
+use bytes::Bytes;
+use rumqttc::QoS;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+/// Message delivered by the loopback broker
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct LoopbackMessage {
+    pub topic: String,
+    pub payload: Bytes,
+    pub retain: bool,
+}
+
+/// True if 'topic' matches the MQTT 'filter' (with '+' and '#' wildcards)
+///
+pub fn topic_matches(filter: &str, topic: &str) -> bool {
+    let mut filter_layers = filter.split('/');
+    let mut topic_layers = topic.split('/');
+    loop {
+        match (filter_layers.next(), topic_layers.next()) {
+            (Some("#"), _) => return true,
+            (Some("+"), Some(_)) => {}
+            (Some(f), Some(t)) if f == t => {}
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
+}
+
+/// Connection to the loopback broker
+///
+struct LoopbackSession {
+    id: usize,
+    filters: Vec<String>,
+    sender: UnboundedSender<LoopbackMessage>,
+}
+
+#[derive(Default)]
+struct LoopbackState {
+    retained: BTreeMap<String, Bytes>,
+    sessions: Vec<LoopbackSession>,
+    next_id: usize,
+}
+
+/// In-memory broker, with publish, subscribe and retained values
+///
+/// Each 'connect' behaves like a MQTT client connection.
+///
+#[derive(Clone, Default)]
+pub struct LoopbackBroker {
+    state: Arc<Mutex<LoopbackState>>,
+}
+
+impl LoopbackBroker {
+    /// Broker without any message
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn state(&self) -> MutexGuard<'_, LoopbackState> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Open a connection, the messages of its subscriptions arrive on the receiver
+    ///
+    pub fn connect(&self) -> (LoopbackTransport, UnboundedReceiver<LoopbackMessage>) {
+        let (sender, receiver) = unbounded_channel();
+        let mut state = self.state();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.sessions.push(LoopbackSession {
+            id,
+            filters: Vec::new(),
+            sender,
+        });
+        let transport = LoopbackTransport {
+            broker: self.clone(),
+            id,
+        };
+        (transport, receiver)
+    }
+
+    /// Retained value of 'topic'
+    ///
+    pub fn retained(&self, topic: &str) -> Option<Bytes> {
+        self.state().retained.get(topic).cloned()
+    }
+
+    fn publish(&self, message: LoopbackMessage) {
+        let mut state = self.state();
+        if message.retain {
+            //
+            // Like MQTT, an empty payload removes the retained value
+            if message.payload.is_empty() {
+                state.retained.remove(&message.topic);
+            } else {
+                state
+                    .retained
+                    .insert(message.topic.clone(), message.payload.clone());
+            }
+        }
+
+        //
+        // Closed connections are forgotten
+        state.sessions.retain(|session| {
+            let matches = session
+                .filters
+                .iter()
+                .any(|filter| topic_matches(filter, &message.topic));
+            !matches || session.sender.send(message.clone()).is_ok()
+        });
+    }
+
+    fn subscribe(&self, id: usize, filter: String) {
+        let mut state = self.state();
+        let retained: Vec<LoopbackMessage> = state
+            .retained
+            .iter()
+            .filter(|(topic, _)| topic_matches(&filter, topic))
+            .map(|(topic, payload)| LoopbackMessage {
+                topic: topic.clone(),
+                payload: payload.clone(),
+                retain: true,
+            })
+            .collect();
+        if let Some(session) = state.sessions.iter_mut().find(|s| s.id == id) {
+            for message in retained {
+                let _ = session.sender.send(message);
+            }
+            session.filters.push(filter);
+        }
+    }
+}
+
+/// Transport of a connection to the loopback broker
+///
+#[derive(Clone)]
+pub struct LoopbackTransport {
+    broker: LoopbackBroker,
+    id: usize,
+}
+
+impl LoopbackTransport {
+    /// Subscribe without waiting, for callers outside of async code
+    ///
+    pub fn subscribe_now<S: Into<String>>(&self, filter: S) {
+        self.broker.subscribe(self.id, filter.into());
+    }
+}
+
+#[async_trait]
+impl MessageTransport for LoopbackTransport {
+    async fn publish(
+        &self,
+        topic: String,
+        _qos: QoS,
+        retain: bool,
+        payload: Vec<u8>,
+    ) -> Result<(), Error> {
+        self.broker.publish(LoopbackMessage {
+            topic,
+            payload: Bytes::from(payload),
+            retain,
+        });
+        Ok(())
+    }
+
+    async fn subscribe(&self, filter: String, _qos: QoS) -> Result<(), Error> {
+        self.broker.subscribe(self.id, filter);
+        Ok(())
+    }
+}
+
+/// Test helper that plays the user of the attributes on a loopback broker
+///
+pub struct LoopbackClient {
+    transport: LoopbackTransport,
+    client: MessageClient,
+    messages: UnboundedReceiver<LoopbackMessage>,
+}
+
+impl LoopbackClient {
+    /// Connect to the broker
+    ///
+    pub fn new(broker: &LoopbackBroker) -> Self {
+        let (transport, messages) = broker.connect();
+        Self {
+            client: MessageClient::new(transport.clone()),
+            transport,
+            messages,
+        }
+    }
+
+    /// Client to publish like the user would do
+    ///
+    pub fn message_client(&self) -> MessageClient {
+        self.client.clone()
+    }
+
+    /// Receive the messages published on the topics matching 'filter'
+    ///
+    pub fn subscribe<S: Into<String>>(&self, filter: S) {
+        self.transport.subscribe_now(filter);
+    }
+
+    /// Receive the values published by the attribute, starting with its retained value
+    ///
+    pub fn watch_attribute(&self, attribute_topic: &str) {
+        self.subscribe(format!("{}/att", attribute_topic));
+    }
+
+    /// Send a command to the attribute
+    ///
+    pub async fn send_command<V: Into<Vec<u8>>>(
+        &self,
+        attribute_topic: &str,
+        payload: V,
+    ) -> Result<(), Error> {
+        self.client
+            .publish(
+                format!("{}/cmd", attribute_topic),
+                QoS::AtMostOnce,
+                false,
+                payload,
+            )
+            .await
+    }
+
+    /// Next message received, Timeout error if none arrives in time
+    ///
+    pub async fn next_message(&mut self, timeout: Duration) -> Result<LoopbackMessage, Error> {
+        match tokio::time::timeout(timeout, self.messages.recv()).await {
+            Ok(Some(message)) => Ok(message),
+            Ok(None) => Err(Error::ChannelError("loopback broker is gone".to_string())),
+            Err(_) => Err(Error::Timeout("no message received".to_string())),
+        }
+    }
+
+    /// Next value published by the attribute, the other messages are skipped
+    ///
+    /// 'watch_attribute' must have been called before.
+    ///
+    pub async fn next_value(
+        &mut self,
+        attribute_topic: &str,
+        timeout: Duration,
+    ) -> Result<Bytes, Error> {
+        let topic_att = format!("{}/att", attribute_topic);
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(tokio::time::Instant::now());
+            let message = self.next_message(remaining).await.map_err(|e| match e {
+                Error::Timeout(_) => Error::Timeout(format!("no value on '{}'", topic_att)),
+                e => e,
+            })?;
+            if message.topic == topic_att {
+                return Ok(message.payload);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{topic_matches, LoopbackBroker, LoopbackClient};
+    use std::time::Duration;
+
+    #[test]
+    fn test_topic_matches() {
+        assert!(topic_matches("pza/+/att", "pza/psu/att"));
+        assert!(topic_matches("pza/#", "pza/psu/control/voltage/att"));
+        assert!(!topic_matches("pza/+/att", "pza/psu/control/att"));
+        assert!(!topic_matches("pza/psu", "pza/psu/att"));
+    }
+
+    #[tokio::test]
+    async fn test_loopback_retained() {
+        let broker = LoopbackBroker::new();
+        let publisher = LoopbackClient::new(&broker);
+        publisher
+            .message_client()
+            .publish("pza/a/att", rumqttc::QoS::AtMostOnce, true, "1")
+            .await
+            .unwrap();
+
+        //
+        // A late subscriber gets the retained value, then the new ones
+        let mut subscriber = LoopbackClient::new(&broker);
+        subscriber.watch_attribute("pza/a");
+        publisher
+            .message_client()
+            .publish("pza/a/att", rumqttc::QoS::AtMostOnce, true, "2")
+            .await
+            .unwrap();
+        let timeout = Duration::from_millis(100);
+        assert_eq!(subscriber.next_value("pza/a", timeout).await.unwrap(), "1");
+        assert_eq!(subscriber.next_value("pza/a", timeout).await.unwrap(), "2");
+        assert!(subscriber.next_value("pza/a", timeout).await.is_err());
+        assert_eq!(broker.retained("pza/a/att").unwrap(), "2");
+    }
+}
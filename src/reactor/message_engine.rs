@@ -1,24 +1,55 @@
+use bytes::Bytes;
 use std::sync::Arc;
 
 use tokio::sync::Mutex;
 
-use crate::MessageDispatcher;
+use super::recorder::MessageRecorder;
+use crate::{log_warn, Logger, MessageDispatcher};
 
 type MessageEventLoop = rumqttc::EventLoop;
 
+/// Give an incoming message to the attributes, recording the commands if needed
+///
+pub async fn dispatch_message(
+    message_dispatcher: &Arc<Mutex<MessageDispatcher>>,
+    recorder: &Option<MessageRecorder>,
+    logger: &Logger,
+    topic: &str,
+    payload: &Bytes,
+) {
+    if let Some(recorder) = recorder {
+        if topic.ends_with("/cmd") {
+            if let Err(e) = recorder.record_command(topic, payload) {
+                log_warn!(logger, "cannot record command on '{}' ({})", topic, e);
+            }
+        }
+    }
+    message_dispatcher
+        .lock()
+        .await
+        .trigger_on_change(topic, payload)
+        .await;
+}
+
 pub struct MessageEngine {
     message_dispatcher: Arc<Mutex<MessageDispatcher>>,
     message_event_loop: MessageEventLoop,
+    recorder: Option<MessageRecorder>,
+    logger: Logger,
 }
 
 impl MessageEngine {
     pub fn new(
         message_dispatcher: Arc<Mutex<MessageDispatcher>>,
         message_event_loop: MessageEventLoop,
+        recorder: Option<MessageRecorder>,
+        logger: Logger,
     ) -> MessageEngine {
         MessageEngine {
             message_dispatcher: message_dispatcher,
             message_event_loop: message_event_loop,
+            recorder,
+            logger,
         }
     }
 
@@ -39,11 +70,14 @@ impl MessageEngine {
                             // let payload_str = std::str::from_utf8(&payload).unwrap();
                             // println!("Received = {:?} {:?}", payload_str, packet.topic);
 
-                            self.message_dispatcher
-                                .lock()
-                                .await
-                                .trigger_on_change(&packet.topic, &packet.payload)
-                                .await;
+                            dispatch_message(
+                                &self.message_dispatcher,
+                                &self.recorder,
+                                &self.logger,
+                                &packet.topic,
+                                &packet.payload,
+                            )
+                            .await;
                         }
                         // rumqttc::Packet::PubAck(_) => todo!(),
                         // rumqttc::Packet::PubRec(_) => todo!(),
@@ -0,0 +1,233 @@
+use crate::{log_warn, Error, Logger};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::sync::mpsc::{channel, Receiver, Sender};
+
+/// Way of a recorded message
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RecordDirection {
+    /// Command received by an attribute
+    Command,
+    /// Value published by an attribute
+    Value,
+}
+
+fn is_false(value: &bool) -> bool {
+    !*value
+}
+
+/// One recorded message, a line of the recording file
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordEntry {
+    /// Unix time in milliseconds
+    ///
+    pub time: i64,
+
+    pub direction: RecordDirection,
+
+    pub topic: String,
+
+    /// Payload, as text when it is valid utf8 (json, numbers...), else in base64
+    ///
+    pub payload: String,
+
+    /// True if 'payload' is encoded in base64
+    ///
+    #[serde(default, skip_serializing_if = "is_false")]
+    pub base64: bool,
+}
+
+impl RecordEntry {
+    /// Create object, timestamped now
+    ///
+    pub fn new<T: Into<String>>(direction: RecordDirection, topic: T, payload: &[u8]) -> Self {
+        let (payload, base64) = match std::str::from_utf8(payload) {
+            Ok(text) => (text.to_string(), false),
+            Err(_) => (BASE64.encode(payload), true),
+        };
+        Self {
+            time: Utc::now().timestamp_millis(),
+            direction,
+            topic: topic.into(),
+            payload,
+            base64,
+        }
+    }
+
+    /// Payload as it has been exchanged
+    ///
+    pub fn payload_bytes(&self) -> Result<Vec<u8>, Error> {
+        match self.base64 {
+            true => BASE64
+                .decode(&self.payload)
+                .map_err(|e| Error::DeserializeError(format!("invalid base64 payload ({})", e))),
+            false => Ok(self.payload.clone().into_bytes()),
+        }
+    }
+}
+
+/// Requests to the writer thread
+///
+enum RecorderRequest {
+    Entry(RecordEntry),
+    Flush(Sender<()>),
+}
+
+/// Write the MQTT traffic of the attributes into a file
+///
+/// The file is a json object per line, so a recording interrupted by a
+/// crash can still be read. Entries are written by a background thread,
+/// recording never waits for the disk.
+///
+#[derive(Clone)]
+pub struct MessageRecorder {
+    requests: Sender<RecorderRequest>,
+}
+
+impl MessageRecorder {
+    /// Create (or truncate) the recording file and start its writer
+    ///
+    pub fn create<P: AsRef<Path>>(path: P) -> Result<Self, Error> {
+        let file = File::create(path.as_ref()).map_err(|e| {
+            Error::Generic(format!(
+                "cannot create recording '{}' ({})",
+                path.as_ref().display(),
+                e
+            ))
+        })?;
+        let (requests, receiver) = channel();
+        std::thread::Builder::new()
+            .name("pza-recorder".to_string())
+            .spawn(move || Self::write_entries(BufWriter::new(file), receiver))
+            .map_err(|e| Error::Generic(format!("cannot start the recorder ({})", e)))?;
+        Ok(Self { requests })
+    }
+
+    /// Writer thread, flushes each time no entry is waiting
+    ///
+    /// It ends when all the recorders are dropped.
+    ///
+    fn write_entries(mut writer: BufWriter<File>, receiver: Receiver<RecorderRequest>) {
+        let logger = Logger::new_for_reactor();
+        while let Ok(request) = receiver.recv() {
+            let mut request = Some(request);
+            while let Some(current) = request.take() {
+                match current {
+                    RecorderRequest::Entry(entry) => {
+                        let written = serde_json::to_string(&entry)
+                            .map_err(|e| e.to_string())
+                            .and_then(|line| {
+                                writeln!(writer, "{}", line).map_err(|e| e.to_string())
+                            });
+                        if let Err(e) = written {
+                            log_warn!(logger, "cannot write the recording ({})", e);
+                        }
+                    }
+                    RecorderRequest::Flush(done) => {
+                        let _ = writer.flush();
+                        let _ = done.send(());
+                    }
+                }
+                request = receiver.try_recv().ok();
+            }
+            if let Err(e) = writer.flush() {
+                log_warn!(logger, "cannot flush the recording ({})", e);
+            }
+        }
+    }
+
+    /// Queue an entry
+    ///
+    pub fn record(&self, entry: &RecordEntry) -> Result<(), Error> {
+        self.requests
+            .send(RecorderRequest::Entry(entry.clone()))
+            .map_err(|_| Error::Generic("the recorder writer is over".to_string()))
+    }
+
+    /// Record a command received on 'topic'
+    ///
+    pub fn record_command(&self, topic: &str, payload: &[u8]) -> Result<(), Error> {
+        self.record(&RecordEntry::new(RecordDirection::Command, topic, payload))
+    }
+
+    /// Record a value published on 'topic'
+    ///
+    pub fn record_value(&self, topic: &str, payload: &[u8]) -> Result<(), Error> {
+        self.record(&RecordEntry::new(RecordDirection::Value, topic, payload))
+    }
+
+    /// Wait until the queued entries are written in the file
+    ///
+    /// Blocking, to be used when the recording is over (before reading it).
+    ///
+    pub fn flush(&self) -> Result<(), Error> {
+        let (done, wait_done) = channel();
+        self.requests
+            .send(RecorderRequest::Flush(done))
+            .map_err(|_| Error::Generic("the recorder writer is over".to_string()))?;
+        wait_done
+            .recv()
+            .map_err(|_| Error::Generic("the recorder writer is over".to_string()))
+    }
+}
+
+/// Read the entries of a recording file
+///
+pub fn read_recording<P: AsRef<Path>>(path: P) -> Result<Vec<RecordEntry>, Error> {
+    let file = File::open(path.as_ref()).map_err(|e| {
+        Error::Generic(format!(
+            "cannot open recording '{}' ({})",
+            path.as_ref().display(),
+            e
+        ))
+    })?;
+    let mut entries = Vec::new();
+    for line in BufReader::new(file).lines() {
+        let line =
+            line.map_err(|e| Error::Generic(format!("cannot read the recording ({})", e)))?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        entries
+            .push(serde_json::from_str(&line).map_err(|e| Error::DeserializeError(e.to_string()))?);
+    }
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{read_recording, MessageRecorder, RecordDirection, RecordEntry};
+
+    #[test]
+    fn test_record_binary_payload() {
+        let path = std::env::temp_dir().join(format!("pza-binary-{}.jsonl", std::process::id()));
+        let recorder = MessageRecorder::create(&path).unwrap();
+        recorder
+            .record_command("pza/dev/mem/cmd", &[0x00, 0xff, 0x10])
+            .unwrap();
+        recorder.record_value("pza/dev/mem/att", b"16").unwrap();
+        recorder.flush().unwrap();
+
+        let entries = read_recording(&path).unwrap();
+        std::fs::remove_file(&path).ok();
+        assert_eq!(entries.len(), 2);
+        assert!(entries[0].base64);
+        assert_eq!(entries[0].payload_bytes().unwrap(), vec![0x00, 0xff, 0x10]);
+        assert_eq!(entries[1].direction, RecordDirection::Value);
+        assert_eq!(
+            entries[1],
+            RecordEntry {
+                time: entries[1].time,
+                ..RecordEntry::new(RecordDirection::Value, "pza/dev/mem/att", b"16")
+            }
+        );
+    }
+}
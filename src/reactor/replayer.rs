@@ -0,0 +1,162 @@
+use super::loopback::{LoopbackBroker, LoopbackClient, LoopbackMessage};
+use super::recorder::{read_recording, RecordDirection, RecordEntry};
+use crate::{MessageClient, MessageDispatcher};
+use bytes::Bytes;
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::UnboundedReceiver;
+use tokio::sync::Mutex;
+
+/// Time given to the driver to react to a replayed command
+///
+pub static DEFAULT_REPLAY_SETTLE_TIME: Duration = Duration::from_millis(50);
+
+/// Values of a topic that differ from the recording
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayMismatch {
+    pub topic: String,
+    pub expected: Vec<String>,
+    pub actual: Vec<String>,
+}
+
+/// Result of a replay
+///
+#[derive(Debug, Clone, Default)]
+pub struct ReplayReport {
+    /// Number of commands fed to the dispatcher
+    ///
+    pub commands: usize,
+
+    /// Topics whose published values differ from the recording
+    ///
+    pub mismatches: Vec<ReplayMismatch>,
+}
+
+impl ReplayReport {
+    /// True if the attributes published exactly the recorded values
+    ///
+    pub fn is_success(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+}
+
+/// Feed recorded commands to a dispatcher, without broker, and check the
+/// values published by the attributes against the recording
+///
+/// The attributes must be created with the client of the replayer
+/// ('message_client'), it publishes on a loopback broker on which the
+/// replayer captures the values.
+///
+pub struct MessageReplayer {
+    entries: Vec<RecordEntry>,
+    client: MessageClient,
+    values: std::sync::Mutex<UnboundedReceiver<LoopbackMessage>>,
+    settle_time: Duration,
+}
+
+impl MessageReplayer {
+    /// Create a replayer for the recorded entries
+    ///
+    pub fn new(entries: Vec<RecordEntry>) -> Self {
+        let broker = LoopbackBroker::new();
+        let (capture, values) = broker.connect();
+        capture.subscribe_now("#");
+        Self {
+            entries,
+            client: LoopbackClient::new(&broker).message_client(),
+            values: std::sync::Mutex::new(values),
+            settle_time: DEFAULT_REPLAY_SETTLE_TIME,
+        }
+    }
+
+    /// Create a replayer for a recording file
+    ///
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, crate::Error> {
+        Ok(Self::new(read_recording(path)?))
+    }
+
+    /// Change the time given to the driver after each command
+    ///
+    pub fn with_settle_time(mut self, settle_time: Duration) -> Self {
+        self.settle_time = settle_time;
+        self
+    }
+
+    /// Client that captures the publications of the attributes
+    ///
+    pub fn message_client(&self) -> MessageClient {
+        self.client.clone()
+    }
+
+    /// Values published on the attribute topics since the last call
+    ///
+    /// They are encoded like the recorder does (base64 if not utf8).
+    ///
+    fn drain_values(&self, values: &mut BTreeMap<String, Vec<String>>) {
+        let mut receiver = self.values.lock().unwrap_or_else(|e| e.into_inner());
+        while let Ok(message) = receiver.try_recv() {
+            if message.topic.ends_with("/att") {
+                values
+                    .entry(message.topic)
+                    .or_default()
+                    .push(RecordEntry::new(RecordDirection::Value, "", &message.payload).payload);
+            }
+        }
+    }
+
+    /// Replay the commands in the recorded order
+    ///
+    pub async fn replay(&self, dispatcher: &Arc<Mutex<MessageDispatcher>>) -> ReplayReport {
+        let mut report = ReplayReport::default();
+        let mut actual = BTreeMap::new();
+        let mut expected: BTreeMap<String, Vec<String>> = BTreeMap::new();
+
+        for entry in &self.entries {
+            match entry.direction {
+                RecordDirection::Command => {
+                    self.drain_values(&mut actual);
+                    // A corrupted base64 payload is given as recorded, the
+                    // attribute reports it as an invalid command
+                    let payload = entry
+                        .payload_bytes()
+                        .unwrap_or_else(|_| entry.payload.clone().into_bytes());
+                    dispatcher
+                        .lock()
+                        .await
+                        .trigger_on_change(&entry.topic, &Bytes::from(payload))
+                        .await;
+                    report.commands += 1;
+                    tokio::time::sleep(self.settle_time).await;
+                }
+                RecordDirection::Value => {
+                    expected
+                        .entry(entry.topic.clone())
+                        .or_default()
+                        .push(entry.payload.clone());
+                }
+            }
+        }
+        self.drain_values(&mut actual);
+
+        //
+        // Compare topic by topic, the order between topics depends on tasks
+        let mut topics: Vec<&String> = expected.keys().chain(actual.keys()).collect();
+        topics.sort();
+        topics.dedup();
+        for topic in topics {
+            let expected = expected.get(topic).cloned().unwrap_or_default();
+            let actual = actual.get(topic).cloned().unwrap_or_default();
+            if expected != actual {
+                report.mismatches.push(ReplayMismatch {
+                    topic: topic.clone(),
+                    expected,
+                    actual,
+                });
+            }
+        }
+        report
+    }
+}
@@ -1,12 +1,42 @@
+use serde::{Deserialize, Serialize};
+
 /// Settings for the reactor
 ///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ReactorSettings {
+    /// Address of the MQTT broker
+    ///
+    #[serde(default = "default_addr")]
     pub addr: String,
+
+    /// Port of the MQTT broker
+    ///
+    #[serde(default = "default_port_mqtt")]
     pub port_mqtt: u16,
 
     /// Namespace on which the reactor must work
     ///
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub namespace: Option<String>,
+
+    /// File in which the attributes MQTT traffic is recorded, none to disable
+    ///
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub record_path: Option<String>,
+}
+
+fn default_addr() -> String {
+    "localhost".to_string()
+}
+
+fn default_port_mqtt() -> u16 {
+    1883
+}
+
+impl Default for ReactorSettings {
+    fn default() -> Self {
+        Self::new(default_addr(), default_port_mqtt(), None)
+    }
 }
 
 impl ReactorSettings {
@@ -15,6 +45,23 @@ impl ReactorSettings {
             addr: addr.into(),
             port_mqtt: port_mqtt,
             namespace: namespace.into(),
+            record_path: None,
+        }
+    }
+
+    /// Record the attributes MQTT traffic into 'path'
+    ///
+    pub fn with_record_path<A: Into<String>>(mut self, path: A) -> Self {
+        self.record_path = Some(path.into());
+        self
+    }
+
+    /// Root topic of the reactor, 'pza' prefixed by the namespace if any
+    ///
+    pub fn root_topic(&self) -> String {
+        match &self.namespace {
+            Some(namespace) if !namespace.is_empty() => format!("{}/pza", namespace),
+            _ => "pza".to_string(),
         }
     }
 }
@@ -0,0 +1,111 @@
+use crate::Error;
+use async_trait::async_trait;
+use rumqttc::{AsyncClient, QoS};
+use std::sync::Arc;
+
+/// Way the attributes exchange messages with the clients
+///
+/// 'MqttTransport' talks to a broker, 'LoopbackTransport' stays in memory so
+/// drivers can be tested without broker.
+///
+#[async_trait]
+pub trait MessageTransport: Send + Sync {
+    /// Publish 'payload' on 'topic'
+    ///
+    async fn publish(
+        &self,
+        topic: String,
+        qos: QoS,
+        retain: bool,
+        payload: Vec<u8>,
+    ) -> Result<(), Error>;
+
+    /// Receive the messages published on the topics matching 'filter'
+    ///
+    async fn subscribe(&self, filter: String, qos: QoS) -> Result<(), Error>;
+}
+
+/// Transport through a MQTT broker
+///
+pub struct MqttTransport {
+    client: AsyncClient,
+}
+
+impl MqttTransport {
+    /// Wrap a MQTT client
+    ///
+    pub fn new(client: AsyncClient) -> Self {
+        Self { client }
+    }
+}
+
+#[async_trait]
+impl MessageTransport for MqttTransport {
+    async fn publish(
+        &self,
+        topic: String,
+        qos: QoS,
+        retain: bool,
+        payload: Vec<u8>,
+    ) -> Result<(), Error> {
+        let pyl_size = payload.len();
+        self.client
+            .publish(topic.clone(), qos, retain, payload)
+            .await
+            .map_err(|e| Error::PublishError {
+                topic,
+                pyl_size,
+                cause: e.to_string(),
+            })
+    }
+
+    async fn subscribe(&self, filter: String, qos: QoS) -> Result<(), Error> {
+        self.client
+            .subscribe(filter, qos)
+            .await
+            .map_err(|e| Error::MessageAttributeSubscribeError(e.to_string()))
+    }
+}
+
+/// Client shared by the reactor and the attributes to send messages
+///
+#[derive(Clone)]
+pub struct MessageClient {
+    transport: Arc<dyn MessageTransport>,
+}
+
+impl MessageClient {
+    /// Create a client over 'transport'
+    ///
+    pub fn new<T: MessageTransport + 'static>(transport: T) -> Self {
+        Self {
+            transport: Arc::new(transport),
+        }
+    }
+
+    /// Publish 'payload' on 'topic'
+    ///
+    pub async fn publish<S: Into<String>, V: Into<Vec<u8>>>(
+        &self,
+        topic: S,
+        qos: QoS,
+        retain: bool,
+        payload: V,
+    ) -> Result<(), Error> {
+        self.transport
+            .publish(topic.into(), qos, retain, payload.into())
+            .await
+    }
+
+    /// Receive the messages published on the topics matching 'filter'
+    ///
+    pub async fn subscribe<S: Into<String>>(&self, filter: S, qos: QoS) -> Result<(), Error> {
+        self.transport.subscribe(filter.into(), qos).await
+    }
+}
+
+impl From<AsyncClient> for MessageClient {
+    fn from(client: AsyncClient) -> Self {
+        Self::new(MqttTransport::new(client))
+    }
+}
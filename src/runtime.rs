@@ -1,20 +1,31 @@
+pub mod alerts;
 pub mod notification;
+pub mod structure;
 
-use crate::{log_debug, log_warn, Logger, Notification, NotificationGroup};
+use crate::{
+    log_debug, log_info, log_warn, AlertNotification, DeviceTree, Error, Instance, JsonAttServer,
+    Logger, Notification, NotificationGroup, NotificationSender, TreeDiff,
+};
 use crate::{
     task_channel::create_task_channel, Factory, ProductionOrder, Reactor, TaskReceiver, TaskResult,
     TaskSender,
 };
+use alerts::{ActiveAlerts, AlertAck};
+use notification::alert::AlertStatus;
+use structure::{PlatformTree, StructureListener};
 // use futures::lock::Mutex;
 use futures::FutureExt;
+use std::path::PathBuf;
 use std::sync::{
     atomic::{AtomicBool, Ordering},
     Arc,
 };
+use std::time::{Duration, SystemTime};
 use tokio::sync::mpsc::Receiver;
 use tokio::sync::mpsc::Sender;
-use tokio::sync::{mpsc::channel, Notify};
+use tokio::sync::{mpsc::channel, Mutex, Notify};
 use tokio::task::JoinSet;
+use tokio::time::MissedTickBehavior;
 
 ///
 ///
@@ -31,6 +42,113 @@ static PROD_ORDER_CHANNEL_SIZE: usize = 64;
 ///
 static NOTIFICATION_CHANNEL_SIZE: usize = 512;
 
+/// Default time given to instances to stop gracefully
+///
+static DEFAULT_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Period of the device tree file modification check
+///
+/// The tree files are polled on their modification time, so a change that keeps
+/// the same modification time (coarse filesystem timestamps, copy preserving times)
+/// is not detected, and a detected change is applied up to one period later.
+///
+static TREE_POLL_PERIOD: Duration = Duration::from_secs(1);
+
+/// Minimal period between two publications of the structure
+///
+static STRUCTURE_PUBLISH_PERIOD: Duration = Duration::from_millis(500);
+
+/// Listeners of the system stop signals (SIGINT and SIGTERM, or Ctrl-C)
+///
+/// Created once before the runtime loop, a signal received while the loop
+/// handles an other event is kept until the next 'recv'.
+///
+#[derive(Default)]
+struct StopSignals {
+    #[cfg(unix)]
+    listeners: Vec<tokio::signal::unix::Signal>,
+    #[cfg(windows)]
+    listeners: Vec<tokio::signal::windows::CtrlC>,
+}
+
+impl StopSignals {
+    /// Start to listen the signals, a signal that cannot be listened is logged and ignored
+    ///
+    fn new(logger: &Logger) -> Self {
+        #[cfg(unix)]
+        let listeners = [
+            tokio::signal::unix::SignalKind::interrupt(),
+            tokio::signal::unix::SignalKind::terminate(),
+        ]
+        .into_iter()
+        .map(tokio::signal::unix::signal)
+        .collect::<Vec<_>>();
+        #[cfg(windows)]
+        let listeners = vec![tokio::signal::windows::ctrl_c()];
+
+        let listeners = listeners
+            .into_iter()
+            .filter_map(|listener| {
+                listener
+                    .map_err(|e| logger.error(format!("Cannot listen stop signals: {:?}", e)))
+                    .ok()
+            })
+            .collect();
+        Self { listeners }
+    }
+
+    /// Wait for the next stop signal, never returns if no signal is listened
+    ///
+    async fn recv(&mut self) {
+        let signals: Vec<_> = self
+            .listeners
+            .iter_mut()
+            .map(|listener| Box::pin(listener.recv()))
+            .collect();
+        if signals.is_empty() {
+            futures::future::pending::<()>().await;
+        }
+        let (signal, _, _) = futures::future::select_all(signals).await;
+        if signal.is_none() {
+            //
+            // The signal driver is gone, do not stop the runtime for that
+            futures::future::pending::<()>().await;
+        }
+    }
+}
+
+/// Handle to control a runtime from outside its task
+///
+#[derive(Clone)]
+pub struct RuntimeHandle {
+    ///
+    /// Flag to know alert the platform, it must stop
+    must_stop: Arc<AtomicBool>,
+    ///
+    /// Wake up the runtime task to process the stop request
+    shutdown_notifier: Arc<Notify>,
+}
+
+impl RuntimeHandle {
+    ///
+    /// Request the runtime to stop
+    ///
+    /// Instances are moved to Stopping, the runtime waits for their cleanup
+    /// (up to the shutdown timeout), flushes logs then 'Runtime::task' returns.
+    ///
+    pub fn shutdown(&self) {
+        self.must_stop.store(true, Ordering::Relaxed);
+        self.shutdown_notifier.notify_one();
+    }
+
+    ///
+    /// True if a stop has been requested
+    ///
+    pub fn is_shutdown_requested(&self) -> bool {
+        self.must_stop.load(Ordering::Relaxed)
+    }
+}
+
 /// Manage the execution instances
 ///
 pub struct Runtime {
@@ -51,6 +169,33 @@ pub struct Runtime {
     /// Flag to know alert the platform, it must stop
     must_stop: Arc<AtomicBool>,
     ///
+    /// Wake up the runtime task when a stop is requested
+    shutdown_notifier: Arc<Notify>,
+    ///
+    /// Time given to instances to stop gracefully
+    shutdown_timeout: Duration,
+    ///
+    /// True if SIGINT/SIGTERM must stop the runtime
+    signal_handling: bool,
+    ///
+    /// True if the logs must be flushed when the runtime is over
+    flush_logs: bool,
+    ///
+    /// Instances produced by this runtime
+    instances: Vec<Instance>,
+    ///
+    /// Device tree file watched by the runtime
+    tree_path: Option<PathBuf>,
+    ///
+    /// Files of the tree with their last modification time
+    tree_signature: Vec<(PathBuf, Option<SystemTime>)>,
+    ///
+    /// Production orders of the tree applied on the instances
+    tree_orders: Vec<ProductionOrder>,
+    ///
+    /// Production orders of the tree queued, applied once their instance is started
+    pending_tree_orders: Vec<ProductionOrder>,
+    ///
     /// Pool
     task_pool: JoinSet<TaskResult>,
     ///
@@ -76,7 +221,37 @@ pub struct Runtime {
     ///
     notifications: Arc<std::sync::Mutex<NotificationGroup>>,
     ///
-    notification_sender: Sender<Notification>,
+    /// Alerts raised and not yet cleared
+    active_alerts: ActiveAlerts,
+    ///
+    /// Name used in the runtime topics, 'platform' or the plugin name
+    name: String,
+    ///
+    /// Attribute '_/alerts/<name>' that publish the active alerts
+    alerts_att: Option<JsonAttServer>,
+    ///
+    /// Instances, classes and attributes built from the notifications
+    structure: PlatformTree,
+    ///
+    /// True if the structure changed since its last publication
+    structure_dirty: bool,
+    ///
+    /// Attribute '_/structure' shared by all the runtimes of the platform
+    structure_att: Option<JsonAttServer>,
+    ///
+    /// Receives the shared structure to merge the local instances into it
+    structure_listener: Arc<Mutex<StructureListener>>,
+    ///
+    /// Notified when an other runtime published the shared structure
+    structure_notifier: Arc<Notify>,
+    ///
+    /// Instances removed by this runtime, to remove from the shared structure
+    forgotten_instances: Vec<String>,
+    ///
+    /// Number of lost notifications already reported in the logs
+    reported_notification_drops: u64,
+    ///
+    notification_sender: NotificationSender,
     ///
     notification_receiver: Option<Receiver<Notification>>,
 }
@@ -88,7 +263,8 @@ impl Runtime {
     pub fn new(factory: Factory, reactor: Reactor) -> Self {
         let (t_tx, t_rx) = create_task_channel::<TaskResult>(TASK_CHANNEL_SIZE);
         let (po_tx, po_rx) = channel::<ProductionOrder>(PROD_ORDER_CHANNEL_SIZE);
-        let (not_tx, not_rx) = channel::<Notification>(NOTIFICATION_CHANNEL_SIZE);
+        let (not_tx, not_rx) = NotificationSender::channel(NOTIFICATION_CHANNEL_SIZE);
+        let structure_notifier = Arc::new(Notify::new());
 
         Self {
             logger: Logger::new_for_runtime(),
@@ -96,6 +272,15 @@ impl Runtime {
             reactor: reactor,
             keep_alive: Arc::new(AtomicBool::new(true)),
             must_stop: Arc::new(AtomicBool::new(false)),
+            shutdown_notifier: Arc::new(Notify::new()),
+            shutdown_timeout: DEFAULT_SHUTDOWN_TIMEOUT,
+            signal_handling: false,
+            flush_logs: false,
+            instances: Vec::new(),
+            tree_path: None,
+            tree_signature: Vec::new(),
+            tree_orders: Vec::new(),
+            pending_tree_orders: Vec::new(),
             task_pool: JoinSet::new(),
             task_sender: t_tx.clone(),
             task_receiver: Some(t_rx),
@@ -103,18 +288,80 @@ impl Runtime {
             production_order_sender: po_tx.clone(),
             production_order_receiver: Some(po_rx),
             notifications: Arc::new(std::sync::Mutex::new(NotificationGroup::new())),
-            notification_sender: not_tx.clone(),
+            active_alerts: ActiveAlerts::new(),
+            name: "platform".to_string(),
+            alerts_att: None,
+            structure: PlatformTree::new(),
+            structure_dirty: false,
+            structure_att: None,
+            structure_listener: Arc::new(Mutex::new(StructureListener::new(
+                structure_notifier.clone(),
+            ))),
+            structure_notifier,
+            forgotten_instances: Vec::new(),
+            reported_notification_drops: 0,
+            notification_sender: not_tx,
             notification_receiver: Some(not_rx),
         }
     }
 
     ///
-    /// Set the plugin name inside the logger
+    /// Set the plugin name inside the logger and the runtime topics
     ///
     pub fn set_plugin<A: Into<String>>(&mut self, text: A) {
+        let text = text.into();
+        self.name = text.clone();
         self.logger.set_plugin(text);
     }
 
+    ///
+    /// Get a handle to stop the runtime, need to be get before task start
+    ///
+    pub fn handle(&self) -> RuntimeHandle {
+        RuntimeHandle {
+            must_stop: self.must_stop.clone(),
+            shutdown_notifier: self.shutdown_notifier.clone(),
+        }
+    }
+
+    ///
+    /// Set the time given to instances to stop gracefully
+    ///
+    pub fn set_shutdown_timeout(&mut self, timeout: Duration) {
+        self.shutdown_timeout = timeout;
+    }
+
+    ///
+    /// Stop the runtime on SIGINT/SIGTERM (Ctrl-C only on windows)
+    ///
+    pub fn enable_signal_handling(&mut self) {
+        self.signal_handling = true;
+    }
+
+    ///
+    /// Flush the log writer when the runtime is over
+    ///
+    /// Logs emitted after are lost, so only the last runtime of the process
+    /// should enable it (not a plugin runtime that may be reloaded).
+    ///
+    pub fn enable_logs_flush(&mut self) {
+        self.flush_logs = true;
+    }
+
+    ///
+    /// Produce the instances of the device tree then watch the tree files
+    ///
+    /// When the files change, only the instances whose entries changed are
+    /// affected: new ones are produced, removed ones are stopped and the ones
+    /// with new settings are remounted.
+    ///
+    /// Changes are detected by polling the modification time of the files
+    /// (see 'TREE_POLL_PERIOD' for the limitations).
+    ///
+    pub fn watch_tree<P: Into<PathBuf>>(&mut self, path: P) {
+        self.tree_path = Some(path.into());
+    }
+
     ///
     /// Getter for 'task_sender', need to be get before task start
     ///
@@ -146,6 +393,15 @@ impl Runtime {
 
         self.reactor.start(self.task_sender.clone()).unwrap();
 
+        //
+        // The runtime works without the alert topics, only log the failure
+        if let Err(e) = self.mount_alert_attributes().await {
+            log_warn!(self.logger, "Cannot mount the alert attributes: {:?}", e);
+        }
+        if let Err(e) = self.mount_structure_attribute().await {
+            log_warn!(self.logger, "Cannot mount the structure attribute: {:?}", e);
+        }
+
         //
         // Remove task receiver from self
         let mut task_receiver = self
@@ -173,8 +429,25 @@ impl Runtime {
                     "Object 'notification_receiver' is 'None'".to_string(),
                 ))?;
 
+        //
+        // Clone what the loop needs, because select borrows 'self'
+        let shutdown_notifier = self.shutdown_notifier.clone();
+        let mut stop_signals = match self.signal_handling {
+            true => StopSignals::new(&self.logger),
+            false => StopSignals::default(),
+        };
+        let tree_watch = self.tree_path.is_some();
+        let mut tree_ticker = tokio::time::interval(TREE_POLL_PERIOD);
+        let structure_notifier = self.structure_notifier.clone();
+        //
+        // When the structure becomes dirty after a long time, publish it at once
+        // then wait a full period before the next publication
+        let mut structure_ticker = tokio::time::interval(STRUCTURE_PUBLISH_PERIOD);
+        structure_ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+
         //
         while self.keep_alive.load(Ordering::Relaxed) {
+            let structure_dirty = self.structure_dirty;
             tokio::select! {
                 //
                 // Manage new task creation requests
@@ -199,45 +472,72 @@ impl Runtime {
 
                     self.logger.debug(format!( "PROD REQUEST ! [{:?}]", production_order ));
 
-                    let name = production_order.as_ref().unwrap().name.clone();
-
-                    // let mut production_order = ProductionOrder::new("panduza.picoha-dio", "testdevice");
-                    // production_order.device_settings = json!({});
-                    let (mut monitor, mut dev) =
-                        self.factory
-                            .produce(self.reactor.clone(), Some(self.notification_sender.clone()), production_order.unwrap());
-
-                    dev.set_plugin(self.logger.get_plugin());
-
-                    // let mut dddddd2 = dev.clone();
-                    self.task_sender
-                        .spawn_with_name(
-                            format!("{}/fsm", name),
-                            async move {
-                                dev.run_fsm().await;
-                                Ok(())
-                            }
-                            .boxed(),
-                        )
-                        .unwrap();
-
-                    self.task_sender
-                        .spawn_with_name(
-                            format!("{}/monitor", name),
-                            async move {
-                                monitor.run().await;
-                                Ok(())
-                            }
-                            .boxed(),
-                        )
-                        .unwrap();
-
+                    //
+                    // A wrong production order must not stop the runtime
+                    if let Some(production_order) = production_order {
+                        let topic = format!("{}/{}", self.reactor.root_topic(), production_order.name);
+                        let name = production_order.name.clone();
+                        if let Err(e) = self.start_instance(production_order) {
+                            //
+                            // A tree order that failed is produced again by the next reload
+                            self.pending_tree_orders.retain(|o| o.name != name);
+                            self.logger.error(format!("Production failure: {:?}", e));
+                            self.push_notification(
+                                AlertNotification::new(topic, format!("{:?}", e)).into(),
+                            )
+                            .await;
+                        }
+                    }
+                },
+                //
+                // Apply device tree modifications
+                //
+                _ = tree_ticker.tick(), if tree_watch => {
+                    if self.tree_has_changed() {
+                        self.reload_tree().await;
+                    }
+                },
+                //
+                // Publish the structure changes together
+                //
+                _ = structure_ticker.tick(), if structure_dirty => {
+                    self.publish_structure().await;
+                },
+                //
+                // Publish again if an other runtime overwrote the local instances
+                //
+                _ = structure_notifier.notified() => {
+                    let shared = self.structure_listener.lock().await.received();
+                    if !shared.contains_part(&self.structure, &self.forgotten_instances) {
+                        self.structure_dirty = true;
+                    }
                 },
                 notif = notification_receiver.recv() => {
 
                     // self.logger.trace(format!( "NOTIF [{:?}]", notif ));
 
-                    self.notifications.lock().unwrap().push(notif.unwrap());
+                    if let Some(notif) = notif {
+                        self.push_notification(notif).await;
+                    }
+
+                    //
+                    // There is room again for the coalesced states
+                    self.notification_sender.flush_pending();
+                    self.report_notification_drops();
+                },
+                //
+                // Stop requested through the runtime handle
+                //
+                _ = shutdown_notifier.notified() => {
+                    break;
+                },
+                //
+                // Stop requested by the system
+                //
+                _ = stop_signals.recv() => {
+                    log_info!(self.logger, "Stop signal received");
+                    self.must_stop.store(true, Ordering::Relaxed);
+                    break;
                 },
                 //
                 // task to create monitor plugin manager notifications
@@ -252,15 +552,455 @@ impl Runtime {
             }
         }
 
+        //
+        // Graceful stop of the instances
+        if self.must_stop.load(Ordering::Relaxed) {
+            self.stop_instances().await;
+        }
+
+        //
+        // Remaining tasks (reactor...) are not needed anymore
+        self.task_pool.abort_all();
+        while self.task_pool.join_next().await.is_some() {}
+
         //
         // Debug log
         self.logger.warn("Runtime over !");
+        if self.flush_logs {
+            crate::tracing::flush();
+        }
 
         //
         // Return ok
         Ok(())
     }
 
+    /// Store the notification for the pullers and follow the alert lifecycles
+    ///
+    async fn push_notification(&mut self, notification: Notification) {
+        if self.structure.apply(&notification) {
+            self.structure_dirty = true;
+        }
+        if let Notification::Alert(alert) = &notification {
+            let changed = self.active_alerts.apply(alert);
+            if changed {
+                self.publish_alerts().await;
+            }
+
+            //
+            // Acknowledgements are shared by all the runtimes, keep only ours
+            if alert.status == AlertStatus::Acknowledged && !changed {
+                return;
+            }
+        }
+        self.notifications.lock().unwrap().push(notification);
+    }
+
+    /// Log the notifications lost since the last report
+    ///
+    fn report_notification_drops(&mut self) {
+        let dropped = self.notification_sender.dropped();
+        if dropped > self.reported_notification_drops {
+            log_warn!(
+                self.logger,
+                "{} notifications lost because the channel was full",
+                dropped - self.reported_notification_drops
+            );
+            self.reported_notification_drops = dropped;
+        }
+    }
+
+    /// Publish the active alerts on '_/alerts/<name>'
+    ///
+    async fn publish_alerts(&self) {
+        if let Some(alerts_att) = &self.alerts_att {
+            if let Err(e) = alerts_att.set(self.active_alerts.to_json_value()).await {
+                log_warn!(self.logger, "Cannot publish the active alerts: {:?}", e);
+            }
+        }
+    }
+
+    /// Create '_/alerts/<name>' (active alerts) and '_/alerts_ack' on the root topic
+    ///
+    /// Each runtime (platform and plugins) publishes its own alerts. The
+    /// '_/alerts_ack' command is shared, a '{"topic": ..., "code": ...}' command
+    /// acknowledges the alert in the runtime that owns it, it goes through the
+    /// notifications like the other alert steps.
+    ///
+    async fn mount_alert_attributes(&mut self) -> Result<(), Error> {
+        let topic = format!("{}/_/alerts", self.reactor.root_topic());
+
+        let alerts_att = self
+            .reactor
+            .create_new_attribute(None)
+            .with_topic(format!("{}/{}", topic, self.name))
+            .with_ro()
+            .with_info("Alerts raised and not yet cleared")
+            .finish_as_json()
+            .await?;
+        alerts_att.set(self.active_alerts.to_json_value()).await?;
+        self.alerts_att = Some(alerts_att);
+
+        let mut ack_att = self
+            .reactor
+            .create_new_attribute(None)
+            .with_topic(format!("{}_ack", topic))
+            .with_wo()
+            .with_info("Acknowledge an alert with {\"topic\": ..., \"code\": ...}")
+            .finish_as_json()
+            .await?;
+
+        let notification_sender = self.notification_sender.clone();
+        let logger = self.logger.clone();
+        self.task_sender.spawn_with_name(
+            "ALERTS ACK",
+            async move {
+                loop {
+                    ack_att.wait_commands().await;
+                    while let Some(command) = ack_att.pop_cmd().await {
+                        match serde_json::from_value::<AlertAck>(command) {
+                            Ok(ack) => {
+                                let alert: AlertNotification = ack.into();
+                                if let Err(e) = notification_sender.send(alert.into()).await {
+                                    log_warn!(
+                                        logger,
+                                        "Cannot forward alert acknowledgement: {:?}",
+                                        e
+                                    );
+                                }
+                            }
+                            Err(e) => {
+                                log_warn!(logger, "Invalid alert acknowledgement: {:?}", e)
+                            }
+                        }
+                    }
+                }
+            }
+            .boxed(),
+        )?;
+        Ok(())
+    }
+
+    /// Create '_/structure' on the root topic
+    ///
+    /// The topic is shared by all the runtimes (platform and plugins), each of
+    /// them merges its own instances into the last published structure.
+    /// The first publication waits for the structure retained on the broker.
+    ///
+    async fn mount_structure_attribute(&mut self) -> Result<(), Error> {
+        let topic = format!("{}/_/structure", self.reactor.root_topic());
+        let structure_att = self
+            .reactor
+            .create_new_attribute(None)
+            .with_topic(topic.clone())
+            .with_ro()
+            .with_info("Instances, classes and attributes of the platform")
+            .finish_as_json()
+            .await?;
+        self.reactor
+            .subscribe_handler(format!("{}/att", topic), self.structure_listener.clone())
+            .await?;
+        self.structure_att = Some(structure_att);
+        self.structure_dirty = true;
+        Ok(())
+    }
+
+    /// Merge the local instances into the shared structure and publish it on '_/structure'
+    ///
+    async fn publish_structure(&mut self) {
+        self.structure_dirty = false;
+        let instances = &self.structure.instances;
+        self.forgotten_instances
+            .retain(|name| !instances.contains_key(name));
+
+        let mut shared = self.structure_listener.lock().await.received();
+        shared.merge_part(&self.structure, &self.forgotten_instances);
+        if let Some(structure_att) = &mut self.structure_att {
+            if let Err(e) = structure_att.set(shared.to_json_value()).await {
+                log_warn!(self.logger, "Cannot publish the structure: {:?}", e);
+            }
+        }
+    }
+
+    /// Produce the instance then spawn its FSM and monitor tasks
+    ///
+    /// The instance is registered only once its tasks are spawned, and names are
+    /// unique: two instances must not share the same topic.
+    ///
+    fn start_instance(&mut self, production_order: ProductionOrder) -> Result<(), Error> {
+        let name = production_order.name.clone();
+        if self.instances.iter().any(|i| i.name() == name) {
+            return Err(Error::InvalidArgument(format!(
+                "Instance '{}' already exists",
+                name
+            )));
+        }
+        let tree_order = self
+            .pending_tree_orders
+            .iter()
+            .find(|o| **o == production_order)
+            .cloned();
+
+        let (mut monitor, mut dev) = self.factory.produce(
+            self.reactor.clone(),
+            Some(self.notification_sender.clone()),
+            production_order,
+        )?;
+
+        dev.set_plugin(self.logger.get_plugin());
+        let instance = dev.clone();
+
+        self.task_sender.spawn_with_name(
+            format!("{}/fsm", name),
+            async move {
+                dev.run_fsm().await;
+                Ok(())
+            }
+            .boxed(),
+        )?;
+
+        self.task_sender.spawn_with_name(
+            format!("{}/monitor", name),
+            async move {
+                monitor.run().await;
+                Ok(())
+            }
+            .boxed(),
+        )?;
+        self.instances.push(instance);
+
+        if let Some(order) = tree_order {
+            self.apply_tree_order(order);
+        }
+        Ok(())
+    }
+
+    /// True if one of the tree files has been modified since the last load
+    ///
+    fn tree_has_changed(&self) -> bool {
+        let files: Vec<PathBuf> = match self.tree_signature.is_empty() {
+            true => self.tree_path.iter().cloned().collect(),
+            false => self.tree_signature.iter().map(|(f, _)| f.clone()).collect(),
+        };
+        Self::signature_of(files) != self.tree_signature
+    }
+
+    /// Modification times of the given files
+    ///
+    fn signature_of(files: Vec<PathBuf>) -> Vec<(PathBuf, Option<SystemTime>)> {
+        files
+            .into_iter()
+            .map(|f| {
+                let modified = std::fs::metadata(&f).and_then(|m| m.modified()).ok();
+                (f, modified)
+            })
+            .collect()
+    }
+
+    /// Load the tree again and apply the differences on the instances
+    ///
+    async fn reload_tree(&mut self) {
+        let path = match &self.tree_path {
+            Some(path) => path.clone(),
+            None => return,
+        };
+
+        let tree = DeviceTree::load(&path);
+
+        //
+        // Even if the load fails, wait for the next modification before retrying
+        self.tree_signature = match &tree {
+            Ok(tree) => Self::signature_of(tree.files()),
+            Err(_) => Self::signature_of(vec![path]),
+        };
+
+        match tree {
+            Ok(tree) => {
+                let orders = tree.production_orders();
+                let diff = TreeDiff::between(&self.expected_tree_orders(), &orders);
+                if !diff.is_empty() {
+                    log_info!(self.logger, "Apply device tree changes {:?}", diff);
+                    self.apply_tree_diff(diff).await;
+                }
+            }
+            Err(e) => {
+                self.logger
+                    .error(format!("Device tree load failure: {:?}", e));
+                self.push_notification(
+                    AlertNotification::new(self.reactor.root_topic(), format!("{:?}", e)).into(),
+                )
+                .await;
+            }
+        }
+    }
+
+    /// Stop, remount or produce the instances affected by the tree changes
+    ///
+    /// An order is kept in 'tree_orders' only once applied, a refused or failed
+    /// change is seen again by the next reload.
+    ///
+    async fn apply_tree_diff(&mut self, diff: TreeDiff) {
+        for name in diff.removed.iter() {
+            self.stop_instance(name, None).await;
+            self.tree_orders.retain(|o| &o.name != name);
+            self.pending_tree_orders.retain(|o| &o.name != name);
+        }
+
+        for order in diff.changed {
+            let mut validated = order.clone();
+            let report = self.factory.validate(&mut validated);
+            if !report.is_valid() {
+                self.logger
+                    .error(format!("Remount of '{}' refused: {}", order.name, report));
+                continue;
+            }
+            match self.instances.iter_mut().find(|i| i.name() == order.name) {
+                Some(instance) => {
+                    instance.remount(validated.settings).await;
+                    self.apply_tree_order(order);
+                }
+                None => self.queue_tree_order(order),
+            }
+        }
+
+        for order in diff.replaced {
+            let name = order.name.clone();
+            self.pending_tree_orders.retain(|o| o.name != name);
+            self.pending_tree_orders.push(order.clone());
+            self.stop_instance(&name, Some(order)).await;
+        }
+
+        for order in diff.added {
+            self.queue_tree_order(order);
+        }
+    }
+
+    /// Orders applied or queued, a queued order replaces the applied one
+    ///
+    fn expected_tree_orders(&self) -> Vec<ProductionOrder> {
+        let mut orders: Vec<ProductionOrder> = self
+            .tree_orders
+            .iter()
+            .filter(|o| !self.pending_tree_orders.iter().any(|p| p.name == o.name))
+            .cloned()
+            .collect();
+        orders.extend(self.pending_tree_orders.iter().cloned());
+        orders
+    }
+
+    /// Queue the production of a tree order, see 'apply_tree_order'
+    ///
+    fn queue_tree_order(&mut self, order: ProductionOrder) {
+        self.pending_tree_orders.retain(|o| o.name != order.name);
+        self.pending_tree_orders.push(order.clone());
+        self.send_production_order(order);
+    }
+
+    /// The tree order is applied on its instance
+    ///
+    fn apply_tree_order(&mut self, order: ProductionOrder) {
+        self.pending_tree_orders.retain(|o| o.name != order.name);
+        match self.tree_orders.iter_mut().find(|o| o.name == order.name) {
+            Some(applied) => *applied = order,
+            None => self.tree_orders.push(order),
+        }
+    }
+
+    /// Stop an instance and forget it
+    ///
+    /// The end of the instance is awaited in a spawned task to keep the runtime loop
+    /// responsive, the 'replacement' is produced only once the instance is stopped.
+    ///
+    async fn stop_instance(&mut self, name: &str, replacement: Option<ProductionOrder>) {
+        let instance = match self.instances.iter().position(|i| i.name() == name) {
+            Some(index) => Some(self.instances.remove(index)),
+            None => None,
+        };
+        if self.structure.remove_instance(name) {
+            self.forgotten_instances.push(name.to_string());
+            self.structure_dirty = true;
+        }
+
+        let Some(mut instance) = instance else {
+            if let Some(order) = replacement {
+                self.send_production_order(order);
+            }
+            return;
+        };
+        instance.stop().await;
+
+        let logger = self.logger.clone();
+        let shutdown_timeout = self.shutdown_timeout;
+        let order_sender = self.production_order_sender.clone();
+        let instance_name = name.to_string();
+        let wait_end = async move {
+            if tokio::time::timeout(shutdown_timeout, instance.wait_stopped())
+                .await
+                .is_err()
+            {
+                log_warn!(logger, "Instance '{}' did not stop in time", instance_name);
+            }
+            if let Some(order) = replacement {
+                if let Err(e) = order_sender.send(order).await {
+                    logger.error(format!("Cannot queue production order: {:?}", e));
+                }
+            }
+            Ok(())
+        };
+        if let Err(e) = self
+            .task_sender
+            .spawn_with_name(format!("{}/stop", name), wait_end.boxed())
+        {
+            self.logger
+                .error(format!("Cannot spawn the stop of '{}': {:?}", name, e));
+        }
+    }
+
+    /// Queue a production order, it will be processed by the runtime loop
+    ///
+    fn send_production_order(&self, order: ProductionOrder) {
+        if let Err(e) = self.production_order_sender.try_send(order) {
+            self.logger
+                .error(format!("Cannot queue production order: {:?}", e));
+        }
+    }
+
+    /// Move all the instances to Stopping and wait for their end
+    ///
+    async fn stop_instances(&mut self) {
+        log_info!(
+            self.logger,
+            "Stopping {} instances...",
+            self.instances.len()
+        );
+        for instance in self.instances.iter_mut() {
+            instance.stop().await;
+        }
+
+        //
+        // Instances tasks keep running inside the pool while waiting for them
+        let instances = self.instances.clone();
+        let all_stopped = async move {
+            for instance in instances.iter() {
+                instance.wait_stopped().await;
+            }
+        };
+        match tokio::time::timeout(self.shutdown_timeout, all_stopped).await {
+            Ok(_) => log_info!(self.logger, "All instances stopped"),
+            Err(_) => {
+                for instance in self.instances.iter() {
+                    if !instance.is_stopped() {
+                        log_warn!(
+                            self.logger,
+                            "Instance '{}' did not stop in time",
+                            instance.name()
+                        );
+                    }
+                }
+            }
+        }
+    }
+
     /// Wait for all tasks to complete
     ///
     async fn end_of_all_tasks(&mut self) -> bool {
@@ -299,3 +1039,135 @@ impl Runtime {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Runtime;
+    use crate::{
+        DriverOperations, Error, Factory, Instance, Producer, ProductionOrder, Props, Reactor,
+        ReactorSettings, TreeDiff,
+    };
+    use async_trait::async_trait;
+    use serde_json::json;
+
+    struct PsuDriver;
+
+    #[async_trait]
+    impl DriverOperations for PsuDriver {
+        async fn mount(&mut self, _instance: Instance) -> Result<(), Error> {
+            Ok(())
+        }
+
+        async fn wait_reboot_event(&mut self, _instance: Instance) {}
+    }
+
+    struct PsuProducer;
+
+    impl Producer for PsuProducer {
+        fn manufacturer(&self) -> String {
+            "test".to_string()
+        }
+
+        fn model(&self) -> String {
+            "psu".to_string()
+        }
+
+        fn description(&self) -> String {
+            "Power supply with a voltage setting".to_string()
+        }
+
+        fn props(&self) -> Props {
+            let mut props = Props::default();
+            props.add_range_prop("voltage", "output voltage", 0.0, 30.0, "V", 5.0);
+            props
+        }
+
+        fn produce(&self) -> Result<Box<dyn DriverOperations>, Error> {
+            Ok(Box::new(PsuDriver))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reload_refused_change() {
+        let path = std::env::temp_dir().join(format!("pza-reload-{}.json", std::process::id()));
+        let write_tree = |voltage: u32| {
+            std::fs::write(
+                &path,
+                format!(
+                    r#"{{ devices: [{{ name: "psu", dref: "test.psu", settings: {{ voltage: {} }} }}] }}"#,
+                    voltage
+                ),
+            )
+            .unwrap()
+        };
+
+        let mut factory = Factory::new();
+        factory.add_producer(Box::new(PsuProducer));
+        let mut runtime = Runtime::new(factory, Reactor::new(ReactorSettings::default()));
+        runtime.watch_tree(&path);
+
+        //
+        // The order is applied once its instance is started
+        write_tree(10);
+        runtime.reload_tree().await;
+        assert!(runtime.tree_orders.is_empty());
+        let order = runtime.pending_tree_orders[0].clone();
+        runtime.start_instance(order).unwrap();
+        assert_eq!(
+            runtime.tree_orders[0].settings,
+            Some(json!({ "voltage": 10 }))
+        );
+
+        //
+        // A refused remount keeps the applied order, the next reload sees the change again
+        write_tree(42);
+        runtime.reload_tree().await;
+        assert_eq!(
+            runtime.tree_orders[0].settings,
+            Some(json!({ "voltage": 10 }))
+        );
+        let orders = crate::DeviceTree::load(&path).unwrap().production_orders();
+        assert_eq!(
+            TreeDiff::between(&runtime.tree_orders, &orders)
+                .changed
+                .len(),
+            1
+        );
+
+        write_tree(12);
+        runtime.reload_tree().await;
+        assert_eq!(
+            runtime.tree_orders[0].settings,
+            Some(json!({ "voltage": 12 }))
+        );
+        assert_eq!(
+            runtime.instances[0].settings().await,
+            Some(json!({ "voltage": 12 }))
+        );
+
+        std::fs::remove_file(&path).ok();
+    }
+
+    #[tokio::test]
+    async fn test_start_instance_once() {
+        let mut factory = Factory::new();
+        factory.add_producer(Box::new(PsuProducer));
+        let mut runtime = Runtime::new(factory, Reactor::new(ReactorSettings::default()));
+        let order = ProductionOrder::new("test.psu", "psu");
+
+        runtime.start_instance(order.clone()).unwrap();
+        assert!(matches!(
+            runtime.start_instance(order),
+            Err(Error::InvalidArgument(_))
+        ));
+        assert_eq!(runtime.instances.len(), 1);
+
+        //
+        // Without task receiver the tasks cannot be spawned, nothing is registered
+        runtime.task_receiver = None;
+        assert!(runtime
+            .start_instance(ProductionOrder::new("test.psu", "other"))
+            .is_err());
+        assert_eq!(runtime.instances.len(), 1);
+    }
+}
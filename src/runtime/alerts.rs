@@ -0,0 +1,129 @@
+use crate::runtime::notification::alert::{AlertNotification, AlertStatus};
+use serde::{Deserialize, Serialize};
+
+/// Payload of the acknowledgement command
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AlertAck {
+    /// Topic of the alert
+    ///
+    pub topic: String,
+
+    /// Code of the alert, none for alerts without code
+    ///
+    #[serde(default)]
+    pub code: Option<String>,
+}
+
+impl From<AlertAck> for AlertNotification {
+    fn from(ack: AlertAck) -> Self {
+        AlertNotification::acknowledged(ack.topic, ack.code)
+    }
+}
+
+/// Alerts raised and not yet cleared
+///
+/// An alert is identified by its topic and its code, raising the same
+/// condition again only updates it.
+///
+/// Alerts without code are transient (nothing can clear them), they are
+/// not kept.
+///
+#[derive(Debug, Default)]
+pub struct ActiveAlerts {
+    alerts: Vec<AlertNotification>,
+}
+
+impl ActiveAlerts {
+    /// No active alert
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Apply a step of the alert lifecycle
+    ///
+    /// Return true if the active alerts changed.
+    ///
+    pub fn apply(&mut self, alert: &AlertNotification) -> bool {
+        if alert.code.is_none() {
+            return false;
+        }
+        let position = self.alerts.iter().position(|a| a.same_condition(alert));
+        match (alert.status, position) {
+            (AlertStatus::Raised, Some(index)) => {
+                //
+                // Keep the acknowledgement if the condition is raised again
+                let acknowledged = self.alerts[index].status == AlertStatus::Acknowledged;
+                self.alerts[index] = alert.clone();
+                if acknowledged {
+                    self.alerts[index].status = AlertStatus::Acknowledged;
+                }
+                true
+            }
+            (AlertStatus::Raised, None) => {
+                self.alerts.push(alert.clone());
+                true
+            }
+            (AlertStatus::Acknowledged, Some(index)) => {
+                let changed = self.alerts[index].status != AlertStatus::Acknowledged;
+                self.alerts[index].status = AlertStatus::Acknowledged;
+                changed
+            }
+            (AlertStatus::Cleared, Some(index)) => {
+                self.alerts.remove(index);
+                true
+            }
+            (_, None) => false,
+        }
+    }
+
+    /// Active alerts, the oldest first
+    ///
+    pub fn alerts(&self) -> &Vec<AlertNotification> {
+        &self.alerts
+    }
+
+    /// Json array published for the dashboards
+    ///
+    pub fn to_json_value(&self) -> serde_json::Value {
+        serde_json::to_value(&self.alerts).unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ActiveAlerts;
+    use crate::runtime::notification::alert::{AlertNotification, AlertSeverity, AlertStatus};
+
+    #[test]
+    fn test_alert_lifecycle() {
+        let mut active = ActiveAlerts::new();
+        let hot = AlertNotification::raised("pza/psu", AlertSeverity::Error, "temp", "70°C");
+        assert!(active.apply(&hot));
+        assert!(active.apply(&AlertNotification::acknowledged(
+            "pza/psu",
+            Some("temp".to_string())
+        )));
+
+        //
+        // Raised again, still acknowledged but with the new message
+        let hotter = AlertNotification::raised("pza/psu", AlertSeverity::Critical, "temp", "90°C");
+        assert!(active.apply(&hotter));
+        assert_eq!(active.alerts().len(), 1);
+        assert_eq!(active.alerts()[0].message, "90°C");
+        assert_eq!(active.alerts()[0].status, AlertStatus::Acknowledged);
+
+        assert!(!active.apply(&AlertNotification::cleared("pza/psu", "other")));
+        assert!(active.apply(&AlertNotification::cleared("pza/psu", "temp")));
+        assert!(active.alerts().is_empty());
+
+        //
+        // Alerts without code are transient
+        assert!(!active.apply(&AlertNotification::new(
+            "pza/psu".to_string(),
+            "glitch".to_string()
+        )));
+        assert!(active.alerts().is_empty());
+    }
+}
@@ -2,13 +2,19 @@ pub mod alert;
 pub mod attribute;
 pub mod class;
 pub mod enablement;
+pub mod filter;
 pub mod group;
+pub mod scan;
+pub mod sender;
 pub mod state;
 
-pub use alert::AlertNotification;
+pub use alert::{AlertNotification, AlertSeverity, AlertStatus};
 pub use attribute::AttributeNotification;
 pub use class::ClassNotification;
 pub use enablement::EnablementNotification;
+pub use filter::{NotificationFilter, NotificationKind};
+pub use scan::ScanNotification;
+pub use sender::NotificationSender;
 pub use state::StateNotification;
 
 use serde::{Deserialize, Serialize};
@@ -39,4 +45,48 @@ pub enum Notification {
     /// can erase the attribute or the class. Choose Enable/Disable instead.
     ///
     Enablement(EnablementNotification),
+
+    /// A scanner reports its progress
+    ///
+    Scan(ScanNotification),
+}
+
+impl Notification {
+    /// Kind of the notification
+    ///
+    pub fn kind(&self) -> NotificationKind {
+        match self {
+            Notification::Alert(_) => NotificationKind::Alert,
+            Notification::State(_) => NotificationKind::State,
+            Notification::Class(_) => NotificationKind::Class,
+            Notification::Attribute(_) => NotificationKind::Attribute,
+            Notification::Enablement(_) => NotificationKind::Enablement,
+            Notification::Scan(_) => NotificationKind::Scan,
+        }
+    }
+
+    /// Topic concerned by the notification, none for scan progress
+    ///
+    pub fn topic(&self) -> Option<String> {
+        match self {
+            Notification::Alert(n) => Some(n.topic.clone()),
+            Notification::State(n) => Some(n.topic.clone()),
+            Notification::Class(n) => Some(n.topic()),
+            Notification::Attribute(n) => Some(n.topic()),
+            Notification::Enablement(n) => Some(n.topic.clone()),
+            Notification::Scan(_) => None,
+        }
+    }
+
+    /// Name of the instance concerned by the notification
+    ///
+    /// It is the layer after 'pza' in the topic, none if the notification is
+    /// not about an instance (scan progress, runtime alerts...)
+    ///
+    pub fn instance_name(&self) -> Option<String> {
+        let topic = self.topic()?;
+        let mut layers = topic.split('/').skip_while(|layer| *layer != "pza");
+        layers.next()?;
+        layers.next().map(|name| name.to_string())
+    }
 }
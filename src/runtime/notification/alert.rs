@@ -1,7 +1,31 @@
 use super::Notification;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// How serious an alert is
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum AlertSeverity {
+    Info,
+    #[default]
+    Warning,
+    Error,
+    Critical,
+}
+
+/// Lifecycle of an alert
+///
+/// A condition (over-temperature...) is 'Raised' once, can be 'Acknowledged'
+/// by the user and is 'Cleared' by the driver when the condition is over.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AlertStatus {
+    #[default]
+    Raised,
+    Acknowledged,
+    Cleared,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AlertNotification {
     /// Class topic
     ///
@@ -10,6 +34,24 @@ pub struct AlertNotification {
     /// Alert message
     ///
     pub message: String,
+
+    /// How serious the alert is
+    ///
+    #[serde(default)]
+    pub severity: AlertSeverity,
+
+    /// Machine readable code of the condition (ex: "over_temperature")
+    ///
+    /// Alerts without code are transient events, they cannot be cleared
+    /// so they never become active alerts.
+    ///
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+
+    /// Step of the alert lifecycle
+    ///
+    #[serde(default)]
+    pub status: AlertStatus,
 }
 
 impl AlertNotification {
@@ -19,8 +61,61 @@ impl AlertNotification {
         Self {
             topic: topic,
             message: message,
+            severity: AlertSeverity::default(),
+            code: None,
+            status: AlertStatus::Raised,
         }
     }
+
+    /// Alert about the condition 'code'
+    ///
+    pub fn raised<T: Into<String>, C: Into<String>, M: Into<String>>(
+        topic: T,
+        severity: AlertSeverity,
+        code: C,
+        message: M,
+    ) -> Self {
+        Self::new(topic.into(), message.into())
+            .with_severity(severity)
+            .with_code(code)
+    }
+
+    /// The condition 'code' is over
+    ///
+    pub fn cleared<T: Into<String>, C: Into<String>>(topic: T, code: C) -> Self {
+        let mut alert = Self::new(topic.into(), String::new()).with_code(code);
+        alert.status = AlertStatus::Cleared;
+        alert
+    }
+
+    /// The user has seen the alert
+    ///
+    pub fn acknowledged<T: Into<String>>(topic: T, code: Option<String>) -> Self {
+        let mut alert = Self::new(topic.into(), String::new());
+        alert.code = code;
+        alert.status = AlertStatus::Acknowledged;
+        alert
+    }
+
+    /// Set the severity
+    ///
+    pub fn with_severity(mut self, severity: AlertSeverity) -> Self {
+        self.severity = severity;
+        self
+    }
+
+    /// Set the code
+    ///
+    pub fn with_code<C: Into<String>>(mut self, code: C) -> Self {
+        self.code = Some(code.into());
+        self
+    }
+
+    /// True if both alerts are about the same condition
+    ///
+    pub fn same_condition(&self, other: &AlertNotification) -> bool {
+        self.topic == other.topic && self.code == other.code
+    }
 }
 
 /// Implicit convertion
@@ -1,6 +1,5 @@
 use super::Notification;
 use serde::{Deserialize, Serialize};
-use serde_json::json;
 
 /// Notification about interface creation
 ///
@@ -13,6 +12,26 @@ pub struct ClassNotification {
     /// Interfaces tags
     ///
     pub tags: Vec<String>,
+
+    /// Description for the user
+    ///
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub info: Option<String>,
+
+    /// Name displayed by the user interfaces instead of the topic layer
+    ///
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+
+    /// Position hint among the sibling classes, lower first
+    ///
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<i32>,
+
+    /// Class specific settings
+    ///
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settings: Option<serde_json::Value>,
 }
 
 impl ClassNotification {
@@ -22,26 +41,55 @@ impl ClassNotification {
         Self {
             topic: topic.into(),
             tags,
+            info: None,
+            display_name: None,
+            order: None,
+            settings: None,
         }
     }
 
+    /// Set the description
+    ///
+    pub fn with_info(mut self, info: Option<String>) -> Self {
+        self.info = info;
+        self
+    }
+
+    /// Set the display name
+    ///
+    pub fn with_display_name(mut self, display_name: Option<String>) -> Self {
+        self.display_name = display_name;
+        self
+    }
+
+    /// Set the ordering hint
+    ///
+    pub fn with_order(mut self, order: Option<i32>) -> Self {
+        self.order = order;
+        self
+    }
+
+    /// Set the settings
+    ///
+    pub fn with_settings(mut self, settings: Option<serde_json::Value>) -> Self {
+        self.settings = settings;
+        self
+    }
+
     /// Topic getter
     ///
     pub fn topic(&self) -> String {
         self.topic.clone()
     }
 
+    /// Metadata of the class, without the topic
+    ///
     pub fn into_json_value(&self) -> serde_json::Value {
-        //
-        // let mut children = serde_json::Map::new();
-        // for e in &self.elements {
-        //     children.insert(e.name().clone(), e.into_json_value());
-        // }
-
-        return json!({
-            "tags": self.tags,
-            // "children": children
-        });
+        let mut value = serde_json::to_value(self).unwrap_or_default();
+        if let Some(map) = value.as_object_mut() {
+            map.remove("topic");
+        }
+        value
     }
 }
 
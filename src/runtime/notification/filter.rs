@@ -0,0 +1,69 @@
+use super::Notification;
+use serde::{Deserialize, Serialize};
+
+/// Kinds of notification, used to filter them
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum NotificationKind {
+    Alert,
+    State,
+    Class,
+    Attribute,
+    Enablement,
+    Scan,
+}
+
+/// Select notifications by kind and by instance
+///
+/// An empty list accepts everything.
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationFilter {
+    /// Accepted kinds
+    ///
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub kinds: Vec<NotificationKind>,
+
+    /// Accepted instance names
+    ///
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub instances: Vec<String>,
+}
+
+impl NotificationFilter {
+    /// Filter that accepts every notification
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Also accept this kind
+    ///
+    pub fn with_kind(mut self, kind: NotificationKind) -> Self {
+        self.kinds.push(kind);
+        self
+    }
+
+    /// Also accept the notifications of this instance
+    ///
+    pub fn with_instance<N: Into<String>>(mut self, name: N) -> Self {
+        self.instances.push(name.into());
+        self
+    }
+
+    /// True if the notification passes the filter
+    ///
+    pub fn matches(&self, notification: &Notification) -> bool {
+        if !self.kinds.is_empty() && !self.kinds.contains(&notification.kind()) {
+            return false;
+        }
+        if !self.instances.is_empty() {
+            return match notification.instance_name() {
+                Some(name) => self.instances.contains(&name),
+                None => false,
+            };
+        }
+        true
+    }
+}
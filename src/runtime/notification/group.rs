@@ -1,11 +1,98 @@
+use std::collections::VecDeque;
 use std::ffi::{c_char, CString};
 
-use super::Notification;
+use serde::{Deserialize, Serialize};
 
+use super::{Notification, NotificationFilter};
+
+/// Number of notifications kept when no capacity is given
+///
+pub const DEFAULT_NOTIFICATION_CAPACITY: usize = 4096;
+
+/// Notification with its position in the stream of the group
+///
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SequencedNotification {
+    /// Sequence number, starts at 1 and increases by one for each notification
+    ///
+    pub sequence: u64,
+
+    /// The notification itself
+    ///
+    pub notification: Notification,
+}
+
+/// Request for 'NotificationGroup::pull_since'
+///
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NotificationQuery {
+    /// Only notifications after this sequence are returned (0 for all)
+    ///
+    pub since: u64,
+
+    /// Notifications that do not match are skipped
+    ///
+    #[serde(flatten)]
+    pub filter: NotificationFilter,
+}
+
+impl NotificationQuery {
+    /// All the notifications after 'since'
+    ///
+    pub fn new(since: u64) -> Self {
+        Self {
+            since,
+            filter: NotificationFilter::default(),
+        }
+    }
+
+    /// Keep only the notifications that match the filter
+    ///
+    pub fn with_filter(mut self, filter: NotificationFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+}
+
+/// Result of a pull
+///
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NotificationPull {
+    /// Notifications after the requested sequence, oldest first
+    ///
+    pub notifications: Vec<SequencedNotification>,
+
+    /// Number of notifications after the requested sequence that have been
+    /// overwritten before the pull, 0 if nothing has been missed
+    ///
+    pub dropped: u64,
+
+    /// Sequence of the last notification pushed in the group,
+    /// to be used as 'since' for the next pull
+    ///
+    pub last_sequence: u64,
+}
+
+/// Bounded buffer of the runtime notifications
+///
+/// When the buffer is full the oldest notifications are overwritten, the
+/// pullers detect it with the sequence numbers.
+///
 pub struct NotificationGroup {
-    elements: Vec<Notification>,
+    elements: VecDeque<SequencedNotification>,
+
+    /// Maximum number of notifications kept
+    ///
+    capacity: usize,
+
+    /// Sequence of the last pushed notification
+    ///
+    last_sequence: u64,
 
-    pulled_elements: Vec<Notification>,
+    /// Sequence of the last notification returned by 'pull'
+    ///
+    pull_cursor: u64,
 
     ///
     /// Keep it here to maintain pointer validity
@@ -13,63 +100,150 @@ pub struct NotificationGroup {
     pulled_elements_serialized: CString,
 }
 
+impl Default for NotificationGroup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl NotificationGroup {
     ///
-    ///
+    /// Group with the default capacity
     ///
     pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_NOTIFICATION_CAPACITY)
+    }
+
+    ///
+    /// Group that keeps at most 'capacity' notifications (at least 1)
+    ///
+    pub fn with_capacity(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
         Self {
-            elements: Vec::new(),
-            pulled_elements: Vec::new(),
+            elements: VecDeque::with_capacity(capacity.min(DEFAULT_NOTIFICATION_CAPACITY)),
+            capacity,
+            last_sequence: 0,
+            pull_cursor: 0,
             pulled_elements_serialized: CString::default(),
         }
     }
 
     ///
+    /// Add a notification, the oldest one is dropped if the group is full
+    ///
+    /// Return the sequence of the notification.
+    ///
+    pub fn push(&mut self, n: Notification) -> u64 {
+        if self.elements.len() >= self.capacity {
+            self.elements.pop_front();
+        }
+        self.last_sequence += 1;
+        self.elements.push_back(SequencedNotification {
+            sequence: self.last_sequence,
+            notification: n,
+        });
+        self.last_sequence
+    }
+
     ///
+    /// Sequence of the last pushed notification (0 if none)
     ///
-    pub fn push(&mut self, n: Notification) {
-        self.elements.push(n);
+    pub fn last_sequence(&self) -> u64 {
+        self.last_sequence
     }
 
     ///
+    /// Notifications after 'query.since' that match the query filter
+    ///
+    /// Nothing is removed from the group, several pullers can follow it with
+    /// their own sequence.
+    ///
+    pub fn pull_since(&self, query: &NotificationQuery) -> NotificationPull {
+        let oldest = self
+            .elements
+            .front()
+            .map(|e| e.sequence)
+            .unwrap_or(self.last_sequence + 1);
+        NotificationPull {
+            notifications: self
+                .elements
+                .iter()
+                .filter(|e| e.sequence > query.since)
+                .filter(|e| query.filter.matches(&e.notification))
+                .cloned()
+                .collect(),
+            dropped: oldest.saturating_sub(query.since + 1),
+            last_sequence: self.last_sequence,
+        }
+    }
+
     ///
+    /// Notifications pushed since the previous call
     ///
     pub fn pull(&mut self) -> Vec<Notification> {
-        self.pulled_elements = self.elements.clone();
-        self.elements.clear();
-        self.pulled_elements.clone()
+        let pulled = self.pull_since(&NotificationQuery::new(self.pull_cursor));
+        self.pull_cursor = pulled.last_sequence;
+        pulled
+            .notifications
+            .into_iter()
+            .map(|e| e.notification)
+            .collect()
     }
 
     ///
+    /// Serialize the result of 'pull_since' for the C interface
     ///
+    /// The pointer is valid until the next call.
     ///
-    pub fn pull_and_serialize(&mut self) -> *const c_char {
-        //
-        // despair logs
-        // println!("DEBUG: pull_and_serialize()");
+    pub fn pull_and_serialize(&mut self, query: &NotificationQuery) -> *const c_char {
+        let pulled = self.pull_since(query);
+        self.pulled_elements_serialized = serde_json::to_string(&pulled)
+            .ok()
+            .and_then(|json_str| CString::new(json_str).ok())
+            .unwrap_or_default();
+        self.pulled_elements_serialized.as_c_str().as_ptr()
+    }
+}
 
-        //
-        // Clone notification elements to make them static
-        self.pulled_elements = self.elements.clone();
+#[cfg(test)]
+mod tests {
+    use super::{NotificationGroup, NotificationQuery};
+    use crate::instance::State;
+    use crate::{AlertNotification, NotificationFilter, NotificationKind, StateNotification};
 
-        //
-        // despair logs
-        // println!("DEBUG: {:?}", self.pulled_elements);
-
-        self.elements.clear();
-        let json_str = serde_json::to_string(&self.pulled_elements)
-            .expect("Failed to serialize pulled_elements to JSON");
-        let as_c_string = CString::new(json_str);
-        match as_c_string {
-            Ok(o) => {
-                self.pulled_elements_serialized = o;
-            }
-            Err(e) => {
-                println!("Failed to serialize pulled_elements to JSON {:?}", e);
-                self.pulled_elements_serialized = CString::default();
-            }
+    #[test]
+    fn test_notification_ring() {
+        let mut group = NotificationGroup::with_capacity(3);
+        for i in 0..5 {
+            group.push(StateNotification::new(format!("pza/dev{}", i), State::Running).into());
         }
-        self.pulled_elements_serialized.as_c_str().as_ptr()
+        group.push(AlertNotification::new("pza/dev1/psu".to_string(), "hot".to_string()).into());
+        assert_eq!(group.last_sequence(), 6);
+
+        //
+        // 1, 2 and 3 have been overwritten
+        let pulled = group.pull_since(&NotificationQuery::new(0));
+        assert_eq!(pulled.dropped, 3);
+        assert_eq!(pulled.notifications.len(), 3);
+        assert_eq!(pulled.notifications[0].sequence, 4);
+
+        let pulled = group.pull_since(&NotificationQuery::new(4));
+        assert_eq!(pulled.dropped, 0);
+        assert_eq!(pulled.notifications.len(), 2);
+
+        let alerts = group.pull_since(
+            &NotificationQuery::new(0)
+                .with_filter(NotificationFilter::new().with_kind(NotificationKind::Alert)),
+        );
+        assert_eq!(alerts.notifications.len(), 1);
+
+        let dev4 = group.pull_since(
+            &NotificationQuery::new(0).with_filter(NotificationFilter::new().with_instance("dev4")),
+        );
+        assert_eq!(dev4.notifications.len(), 1);
+        assert_eq!(dev4.notifications[0].sequence, 5);
+
+        assert_eq!(group.pull().len(), 3);
+        assert!(group.pull().is_empty());
     }
 }
@@ -0,0 +1,54 @@
+use super::Notification;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Progress of a scanner
+///
+pub struct ScanNotification {
+    /// Name of the scanner
+    ///
+    pub scanner: String,
+
+    /// Steps already done
+    ///
+    pub step: u32,
+
+    /// Total number of steps (0 if unknown)
+    ///
+    pub total: u32,
+
+    /// What the scanner is doing
+    ///
+    pub message: String,
+
+    /// True when the scanner has ended
+    ///
+    pub finished: bool,
+}
+
+impl ScanNotification {
+    /// Create new object
+    ///
+    pub fn new<A: Into<String>, M: Into<String>>(
+        scanner: A,
+        step: u32,
+        total: u32,
+        message: M,
+    ) -> Self {
+        Self {
+            scanner: scanner.into(),
+            step,
+            total,
+            message: message.into(),
+            finished: false,
+        }
+    }
+}
+
+/// Implicit convertion
+///
+impl From<ScanNotification> for Notification {
+    fn from(notification: ScanNotification) -> Notification {
+        Notification::Scan(notification)
+    }
+}
@@ -0,0 +1,224 @@
+use super::Notification;
+use crate::Error;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::mpsc::{channel, Receiver, Sender};
+
+/// Time given to the runtime to make room in a full channel
+///
+pub static NOTIFICATION_SEND_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Send notifications to the runtime without panicking when the channel is full
+///
+/// - State and structure (class, attribute, enablement) notifications are
+///   coalesced: only the last one of each kind and topic is kept until the
+///   channel has room again, so they are never lost.
+/// - Other notifications wait for room (up to 'NOTIFICATION_SEND_TIMEOUT')
+///   with 'send', or fail immediately with 'try_send'.
+/// - Lost notifications are counted.
+///
+#[derive(Clone, Debug)]
+pub struct NotificationSender {
+    sender: Sender<Notification>,
+
+    /// Coalesced notifications waiting for room in the channel, one per kind and topic
+    ///
+    pending_notifications: Arc<Mutex<Vec<Notification>>>,
+
+    /// Number of notifications lost
+    ///
+    dropped: Arc<AtomicU64>,
+}
+
+impl NotificationSender {
+    /// Wrap the sender of a notification channel
+    ///
+    pub fn new(sender: Sender<Notification>) -> Self {
+        Self {
+            sender,
+            pending_notifications: Arc::new(Mutex::new(Vec::new())),
+            dropped: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Create a notification channel
+    ///
+    pub fn channel(size: usize) -> (Self, Receiver<Notification>) {
+        let (tx, rx) = channel(size);
+        (Self::new(tx), rx)
+    }
+
+    /// Number of notifications lost since the creation of the channel
+    ///
+    pub fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+
+    fn pending(&self) -> std::sync::MutexGuard<'_, Vec<Notification>> {
+        self.pending_notifications
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Send the coalesced notifications that are waiting, in order, while there is room
+    ///
+    /// Return true if no notification is waiting anymore.
+    ///
+    pub fn flush_pending(&self) -> bool {
+        let mut pending = self.pending();
+        while !pending.is_empty() {
+            match self.sender.try_send(pending[0].clone()) {
+                Ok(_) => {
+                    pending.remove(0);
+                }
+                Err(TrySendError::Full(_)) => return false,
+                Err(TrySendError::Closed(_)) => {
+                    self.dropped
+                        .fetch_add(pending.len() as u64, Ordering::Relaxed);
+                    pending.clear();
+                }
+            }
+        }
+        true
+    }
+
+    /// True if only the last notification of its kind and topic matters
+    ///
+    fn is_coalesced(notification: &Notification) -> bool {
+        matches!(
+            notification,
+            Notification::State(_)
+                | Notification::Class(_)
+                | Notification::Attribute(_)
+                | Notification::Enablement(_)
+        )
+    }
+
+    /// Keep only the last notification of the kind and topic, after the other pending ones
+    ///
+    fn coalesce(&self, notification: Notification) {
+        let mut pending = self.pending();
+        pending.retain(|p| p.kind() != notification.kind() || p.topic() != notification.topic());
+        pending.push(notification);
+    }
+
+    fn closed_error() -> Error {
+        Error::ChannelError("notification channel is closed".to_string())
+    }
+
+    /// Send if there is room, else give the notification back (except coalesced ones)
+    ///
+    fn send_or_give_back(&self, notification: Notification) -> Result<Option<Notification>, Error> {
+        //
+        // Coalesced notifications must not overtake the pending ones
+        let flushed = self.flush_pending();
+        if !flushed && Self::is_coalesced(&notification) {
+            self.coalesce(notification);
+            return Ok(None);
+        }
+
+        match self.sender.try_send(notification) {
+            Ok(_) => Ok(None),
+            Err(TrySendError::Full(notification)) if Self::is_coalesced(&notification) => {
+                self.coalesce(notification);
+                Ok(None)
+            }
+            Err(TrySendError::Full(notification)) => Ok(Some(notification)),
+            Err(TrySendError::Closed(_)) => Err(Self::closed_error()),
+        }
+    }
+
+    /// Send without waiting, a full channel loses the notification (except coalesced ones)
+    ///
+    pub fn try_send(&self, notification: Notification) -> Result<(), Error> {
+        match self.send_or_give_back(notification)? {
+            None => Ok(()),
+            Some(_) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                Err(Error::ChannelError(
+                    "notification channel is full, notification dropped".to_string(),
+                ))
+            }
+        }
+    }
+
+    /// Send, waiting for room in the channel if needed (except coalesced ones)
+    ///
+    pub async fn send(&self, notification: Notification) -> Result<(), Error> {
+        let notification = match self.send_or_give_back(notification)? {
+            None => return Ok(()),
+            Some(notification) => notification,
+        };
+        match tokio::time::timeout(NOTIFICATION_SEND_TIMEOUT, self.sender.send(notification)).await
+        {
+            Ok(Ok(_)) => Ok(()),
+            Ok(Err(_)) => Err(Self::closed_error()),
+            Err(_) => {
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                Err(Error::Timeout(
+                    "notification channel is still full, notification dropped".to_string(),
+                ))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::NotificationSender;
+    use crate::instance::State;
+    use crate::runtime::notification::attribute::{AttributeMode, AttributeNotification};
+    use crate::{AlertNotification, Notification, StateNotification};
+
+    fn state(topic: &str, state: State) -> Notification {
+        StateNotification::new(topic.to_string(), state).into()
+    }
+
+    #[tokio::test]
+    async fn test_notification_overflow() {
+        let (sender, mut receiver) = NotificationSender::channel(1);
+        sender.try_send(state("pza/a", State::Booting)).unwrap();
+
+        //
+        // Channel full: states are coalesced, others are dropped
+        sender.try_send(state("pza/a", State::Connecting)).unwrap();
+        sender.try_send(state("pza/a", State::Running)).unwrap();
+        let alert = AlertNotification::new("pza/a".to_string(), "x".to_string());
+        assert!(sender.try_send(alert.clone().into()).is_err());
+        assert!(sender.send(alert.into()).await.is_err());
+        assert_eq!(sender.dropped(), 2);
+
+        assert!(matches!(
+            receiver.recv().await,
+            Some(Notification::State(s)) if matches!(s.state, State::Booting)
+        ));
+        assert!(sender.flush_pending());
+        assert!(matches!(
+            receiver.recv().await,
+            Some(Notification::State(s)) if matches!(s.state, State::Running)
+        ));
+
+        //
+        // Attribute creations are kept while the channel is full
+        sender.try_send(state("pza/a", State::Booting)).unwrap();
+        for info in ["first", "last"] {
+            let attribute = AttributeNotification::new(
+                "pza/a/x",
+                "si",
+                AttributeMode::ReadOnly,
+                Some(info.to_string()),
+                None,
+            );
+            sender.send(attribute.into()).await.unwrap();
+        }
+        assert_eq!(sender.dropped(), 2);
+        receiver.recv().await.unwrap();
+        assert!(sender.flush_pending());
+        assert!(matches!(
+            receiver.recv().await,
+            Some(Notification::Attribute(a)) if a.info().as_deref() == Some("last")
+        ));
+    }
+}
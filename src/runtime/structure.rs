@@ -0,0 +1,457 @@
+use crate::instance::State;
+use crate::runtime::notification::attribute::{AttributeMode, AttributeNotification};
+use crate::runtime::notification::{ClassNotification, EnablementNotification, StateNotification};
+use crate::{Error, MessageHandler, Notification};
+use async_trait::async_trait;
+use bytes::Bytes;
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use tokio::sync::Notify;
+
+/// Split a topic into the instance name and the layers under the instance
+///
+/// 'ns/pza/psu/control/voltage' => ("psu", ["control", "voltage"])
+///
+fn split_topic(topic: &str) -> Option<(String, Vec<String>)> {
+    let mut layers = topic.split('/').skip_while(|layer| *layer != "pza");
+    layers.next()?;
+    let instance = layers.next()?.to_string();
+    Some((instance, layers.map(|l| l.to_string()).collect()))
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+fn default_true() -> bool {
+    true
+}
+
+/// Attribute of the platform structure
+///
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttributeNode {
+    /// Type of the attribute (string, json, si...)
+    ///
+    #[serde(rename = "type")]
+    pub typee: String,
+
+    /// RO, WO or RW
+    ///
+    pub mode: AttributeMode,
+
+    /// Description for the user
+    ///
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub info: Option<String>,
+
+    /// Type dependant settings (unit, choices...)
+    ///
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settings: Option<JsonValue>,
+
+    /// False when the driver has disabled the attribute
+    ///
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub enabled: bool,
+}
+
+impl From<&AttributeNotification> for AttributeNode {
+    fn from(notification: &AttributeNotification) -> Self {
+        Self {
+            typee: notification.typee().clone(),
+            mode: notification.mode().clone(),
+            info: notification.info().clone(),
+            settings: notification.settings().clone(),
+            enabled: true,
+        }
+    }
+}
+
+/// Classes and attributes under an instance or a class
+///
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Children {
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub classes: BTreeMap<String, ClassNode>,
+
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub attributes: BTreeMap<String, AttributeNode>,
+}
+
+impl Children {
+    /// Class at 'path', created with its parents if needed
+    ///
+    fn class_entry(&mut self, path: &[String]) -> Option<&mut ClassNode> {
+        let (first, rest) = path.split_first()?;
+        let class = self.classes.entry(first.clone()).or_default();
+        if rest.is_empty() {
+            Some(class)
+        } else {
+            class.children.class_entry(rest)
+        }
+    }
+
+    /// Children of the class at 'path', or self if the path is empty
+    ///
+    fn children_entry(&mut self, path: &[String]) -> Option<&mut Children> {
+        if path.is_empty() {
+            return Some(self);
+        }
+        self.class_entry(path).map(|class| &mut class.children)
+    }
+
+    /// Existing class at 'path'
+    ///
+    fn find_class_mut(&mut self, path: &[String]) -> Option<&mut ClassNode> {
+        let (first, rest) = path.split_first()?;
+        let class = self.classes.get_mut(first)?;
+        if rest.is_empty() {
+            Some(class)
+        } else {
+            class.children.find_class_mut(rest)
+        }
+    }
+
+    /// Existing attribute at 'path'
+    ///
+    fn find_attribute_mut(&mut self, path: &[String]) -> Option<&mut AttributeNode> {
+        let (leaf, parents) = path.split_last()?;
+        let children = match parents.is_empty() {
+            true => self,
+            false => &mut self.find_class_mut(parents)?.children,
+        };
+        children.attributes.get_mut(leaf)
+    }
+}
+
+/// Class of the platform structure
+///
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ClassNode {
+    /// Tags given by the driver
+    ///
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+
+    /// Description for the user
+    ///
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub info: Option<String>,
+
+    /// Name displayed instead of the topic layer
+    ///
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub display_name: Option<String>,
+
+    /// Position hint among the sibling classes
+    ///
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub order: Option<i32>,
+
+    /// Class specific settings
+    ///
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settings: Option<JsonValue>,
+
+    /// False when the driver has disabled the class
+    ///
+    #[serde(default = "default_true", skip_serializing_if = "is_true")]
+    pub enabled: bool,
+
+    #[serde(flatten)]
+    pub children: Children,
+}
+
+impl Default for ClassNode {
+    fn default() -> Self {
+        Self {
+            tags: Vec::new(),
+            info: None,
+            display_name: None,
+            order: None,
+            settings: None,
+            enabled: true,
+            children: Children::default(),
+        }
+    }
+}
+
+/// Instance of the platform structure
+///
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct InstanceNode {
+    /// Last state reported by the instance
+    ///
+    #[serde(default)]
+    pub state: State,
+
+    #[serde(flatten)]
+    pub children: Children,
+}
+
+/// Hierarchy of the instances, classes and attributes of the platform
+///
+/// It is built from the runtime notifications, so every consumer can get the
+/// whole structure at once instead of replaying the notifications.
+///
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PlatformTree {
+    pub instances: BTreeMap<String, InstanceNode>,
+}
+
+impl PlatformTree {
+    /// Empty structure
+    ///
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Update the structure with the notification
+    ///
+    /// Return true if the structure changed.
+    ///
+    pub fn apply(&mut self, notification: &Notification) -> bool {
+        match notification {
+            Notification::State(n) => self.apply_state(n),
+            Notification::Class(n) => self.apply_class(n),
+            Notification::Attribute(n) => self.apply_attribute(n),
+            Notification::Enablement(n) => self.apply_enablement(n),
+            Notification::Alert(_) | Notification::Scan(_) => false,
+        }
+    }
+
+    fn apply_state(&mut self, notification: &StateNotification) -> bool {
+        match split_topic(&notification.topic) {
+            //
+            // Late notification of a removed instance
+            Some((instance, _))
+                if matches!(notification.state, State::Stopping)
+                    && !self.instances.contains_key(&instance) =>
+            {
+                false
+            }
+            Some((instance, _)) => {
+                self.instances.entry(instance).or_default().state = notification.state.clone();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn apply_class(&mut self, notification: &ClassNotification) -> bool {
+        let (instance, path) = match split_topic(&notification.topic) {
+            Some(split) => split,
+            None => return false,
+        };
+        let node = self.instances.entry(instance).or_default();
+        match node.children.class_entry(&path) {
+            Some(class) => {
+                class.tags = notification.tags.clone();
+                class.info = notification.info.clone();
+                class.display_name = notification.display_name.clone();
+                class.order = notification.order;
+                class.settings = notification.settings.clone();
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn apply_attribute(&mut self, notification: &AttributeNotification) -> bool {
+        let (instance, path) = match split_topic(notification.name()) {
+            Some(split) => split,
+            None => return false,
+        };
+        let (leaf, parents) = match path.split_last() {
+            Some(split) => split,
+            None => return false,
+        };
+        let node = self.instances.entry(instance).or_default();
+        match node.children.children_entry(parents) {
+            Some(children) => {
+                children
+                    .attributes
+                    .insert(leaf.clone(), AttributeNode::from(notification));
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn apply_enablement(&mut self, notification: &EnablementNotification) -> bool {
+        let (instance, path) = match split_topic(&notification.topic) {
+            Some(split) => split,
+            None => return false,
+        };
+        let children = match self.instances.get_mut(&instance) {
+            Some(node) => &mut node.children,
+            None => return false,
+        };
+        if let Some(attribute) = children.find_attribute_mut(&path) {
+            attribute.enabled = notification.enabled;
+            return true;
+        }
+        if let Some(class) = children.find_class_mut(&path) {
+            class.enabled = notification.enabled;
+            return true;
+        }
+        false
+    }
+
+    /// Forget a stopped instance
+    ///
+    /// Return true if the instance was in the structure.
+    ///
+    pub fn remove_instance(&mut self, name: &str) -> bool {
+        self.instances.remove(name).is_some()
+    }
+
+    /// Json representation published on '_/structure'
+    ///
+    pub fn to_json_value(&self) -> JsonValue {
+        serde_json::to_value(self).unwrap_or_default()
+    }
+
+    /// Put the instances of 'part' (the structure of one runtime) into the
+    /// shared structure, and remove the 'forgotten' instances of this runtime
+    ///
+    pub fn merge_part(&mut self, part: &PlatformTree, forgotten: &[String]) {
+        for name in forgotten {
+            self.instances.remove(name);
+        }
+        for (name, node) in part.instances.iter() {
+            self.instances.insert(name.clone(), node.clone());
+        }
+    }
+
+    /// True if the shared structure contains 'part' as it is and none of the
+    /// 'forgotten' instances
+    ///
+    pub fn contains_part(&self, part: &PlatformTree, forgotten: &[String]) -> bool {
+        let json_of = |node: &InstanceNode| serde_json::to_value(node).ok();
+        forgotten
+            .iter()
+            .all(|name| !self.instances.contains_key(name))
+            && part
+                .instances
+                .iter()
+                .all(|(name, node)| self.instances.get(name).map(json_of) == Some(json_of(node)))
+    }
+}
+
+/// Receives the structure shared by all the runtimes of the platform
+///
+/// Each runtime merges its own instances into the last received structure
+/// before publishing it, and publishes again when a concurrent publication
+/// of an other runtime lost its instances.
+///
+pub struct StructureListener {
+    /// Last structure received, none before the first message
+    ///
+    received: Option<PlatformTree>,
+
+    /// Notified on each received structure
+    ///
+    notifier: Arc<Notify>,
+}
+
+impl StructureListener {
+    /// Listener that notifies 'notifier' on each message
+    ///
+    pub fn new(notifier: Arc<Notify>) -> Self {
+        Self {
+            received: None,
+            notifier,
+        }
+    }
+
+    /// Last structure received, empty before the first message
+    ///
+    pub fn received(&self) -> PlatformTree {
+        self.received.clone().unwrap_or_default()
+    }
+}
+
+#[async_trait]
+impl MessageHandler for StructureListener {
+    async fn on_message(&mut self, data: &Bytes) -> Result<(), Error> {
+        let tree = serde_json::from_slice(data)
+            .map_err(|e| Error::DeserializeError(format!("Invalid structure {:?}", e)))?;
+        self.received = Some(tree);
+        self.notifier.notify_one();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::PlatformTree;
+    use crate::instance::State;
+    use crate::runtime::notification::attribute::{AttributeMode, AttributeNotification};
+    use crate::runtime::notification::{
+        ClassNotification, EnablementNotification, StateNotification,
+    };
+
+    #[test]
+    fn test_platform_tree() {
+        let mut tree = PlatformTree::new();
+        tree.apply(&StateNotification::new("pza/psu".to_string(), State::Running).into());
+        tree.apply(
+            &ClassNotification::new("pza/psu/control", vec!["power".to_string()])
+                .with_display_name(Some("Control".to_string()))
+                .with_order(Some(1))
+                .into(),
+        );
+        tree.apply(
+            &AttributeNotification::new(
+                "pza/psu/control/voltage",
+                "si",
+                AttributeMode::ReadWrite,
+                Some("Output voltage".to_string()),
+                None,
+            )
+            .into(),
+        );
+        tree.apply(&EnablementNotification::new("pza/psu/control/voltage", false).into());
+        assert!(!tree.apply(&EnablementNotification::new("pza/other/x", false).into()));
+
+        let json = tree.to_json_value();
+        let psu = &json["instances"]["psu"];
+        assert_eq!(psu["state"], "Running");
+        assert_eq!(psu["classes"]["control"]["tags"][0], "power");
+        assert_eq!(psu["classes"]["control"]["display_name"], "Control");
+        assert_eq!(psu["classes"]["control"]["order"], 1);
+        assert!(psu["classes"]["control"].get("info").is_none());
+        let voltage = &psu["classes"]["control"]["attributes"]["voltage"];
+        assert_eq!(voltage["type"], "si");
+        assert_eq!(voltage["mode"], "RW");
+        assert_eq!(voltage["enabled"], false);
+    }
+
+    #[test]
+    fn test_merge_runtime_parts() {
+        let mut platform = PlatformTree::new();
+        platform.apply(&StateNotification::new("pza/psu".to_string(), State::Running).into());
+        let mut plugin = PlatformTree::new();
+        plugin.apply(&StateNotification::new("pza/dmm".to_string(), State::Running).into());
+        plugin.apply(&StateNotification::new("pza/old".to_string(), State::Running).into());
+
+        let mut shared = PlatformTree::new();
+        shared.merge_part(&plugin, &[]);
+        shared.merge_part(&platform, &[]);
+        assert_eq!(shared.instances.len(), 3);
+        assert!(shared.contains_part(&platform, &[]));
+        assert!(shared.contains_part(&plugin, &[]));
+
+        plugin.remove_instance("old");
+        let forgotten = vec!["old".to_string()];
+        assert!(!shared.contains_part(&plugin, &forgotten));
+        shared.merge_part(&plugin, &forgotten);
+        assert!(shared.contains_part(&plugin, &forgotten));
+        assert!(shared.contains_part(&platform, &[]));
+        assert_eq!(shared.instances.len(), 2);
+    }
+}
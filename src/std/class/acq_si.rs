@@ -48,6 +48,7 @@ pub async fn mount<A: Into<String>, N: Into<String>, C: Container, I: SiDataRead
     let mut class_acq_si = parent
         .create_class(&name.into())
         .with_tag("acq_si")
+        .with_info("Acquire a measure with its unit")
         .finish()
         .await;
     let logger = class_acq_si.logger().clone();
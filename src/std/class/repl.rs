@@ -27,6 +27,7 @@ pub async fn mount<A: Into<String>, C: Container, I: ReplProtocol + 'static>(
     let mut class_repl = parent
         .create_class(&name.into())
         .with_tag("REPL")
+        .with_info("Send a command and read the response")
         .finish()
         .await;
     let logger = class_repl.logger().clone();
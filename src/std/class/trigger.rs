@@ -29,6 +29,7 @@ pub async fn mount<C: Container, T: Triggerable + 'static>(
     let mut class_trigger = parent
         .create_class("trigger")
         .with_tag("trigger")
+        .with_info("Trigger an action on the device")
         .finish()
         .await;
     let logger = class_trigger.logger().clone();
@@ -1,6 +1,7 @@
 mod csv_formatter;
 mod logger;
 mod multi_writer;
+mod worker;
 
 pub use logger::AttributeLogger;
 pub use logger::DriverLogger;
@@ -10,24 +11,33 @@ pub use logger::Logger;
 
 use csv_formatter::CSVFormatter;
 use multi_writer::MultiWriter;
-use tracing_appender::non_blocking::WorkerGuard;
+use std::sync::Once;
+use worker::WorkerWriter;
 
-/// Keep the non_blocking writer guard alive as long as the application is alive
-/// Else writer stop working
+/// Flush the logs still waiting and stop the log writer thread
 ///
-static mut GUARD: Option<WorkerGuard> = None;
+/// Must be called at the end of the application, or before a plugin library
+/// is unloaded. Logs emitted after are lost until the next 'init'.
+///
+pub fn flush() {
+    worker::stop();
+}
+
+/// The subscriber must be installed only once per process
+///
+static INIT: Once = Once::new();
 
 /// Function to initiliaze tracing for the application
 ///
+/// The subscriber is installed by the first call. The log writer thread is
+/// started again if 'flush' stopped it, with the configuration of this call.
+///
 pub fn init(enable_stdout: bool, enable_broker_log: bool, debug: bool, trace: bool) {
-    let multiw = MultiWriter::new(enable_stdout, enable_broker_log, debug, trace);
-
-    let (non_blocking, guard) = tracing_appender::non_blocking(multiw);
-
-    unsafe {
-        GUARD = Some(guard);
-    }
+    worker::start(|| MultiWriter::new(enable_stdout, enable_broker_log, debug, trace));
+    INIT.call_once(init_subscriber);
+}
 
+fn init_subscriber() {
     let subscriber = tracing_subscriber::fmt()
         // .with_max_level(tracing::Level::TRACE)
         .with_max_level(tracing::Level::TRACE)
@@ -40,7 +50,7 @@ pub fn init(enable_stdout: bool, enable_broker_log: bool, debug: bool, trace: bo
         // Build the subscriber
         .event_format(CSVFormatter {})
         // Custom writer
-        .with_writer(non_blocking)
+        .with_writer(WorkerWriter)
         // Ok
         .finish();
     // use that subscriber to process traces emitted after this point
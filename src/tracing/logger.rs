@@ -52,6 +52,12 @@ impl Logger {
         Self::new("Runtime", "", "", "")
     }
 
+    /// Create a logger configured for reactor
+    ///
+    pub fn new_for_reactor() -> Self {
+        Self::new("Reactor", "", "", "")
+    }
+
     /// Create a logger configured for instance from its name
     ///
     pub fn new_for_instance<A: Into<String>>(name: A) -> Self {
@@ -184,6 +190,9 @@ impl FactoryLogger {
             base: Logger::new("Factory", "", "", ""),
         }
     }
+    pub fn warn<A: Into<String>>(&self, text: A) {
+        self.base.warn(text);
+    }
     pub fn info<A: Into<String>>(&self, text: A) {
         self.base.info(text);
     }
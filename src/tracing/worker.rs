@@ -0,0 +1,95 @@
+use super::multi_writer::MultiWriter;
+use std::io::Write;
+use std::sync::mpsc::{channel, Sender};
+use std::sync::{Mutex, MutexGuard};
+use std::thread::JoinHandle;
+use tracing_subscriber::fmt::MakeWriter;
+
+/// Messages to the writer thread
+///
+enum WorkerMessage {
+    Line(Vec<u8>),
+    Shutdown,
+}
+
+/// Thread that writes the logs, so logging never waits for the disk or the console
+///
+struct Worker {
+    sender: Sender<WorkerMessage>,
+    thread: JoinHandle<()>,
+}
+
+/// Running writer thread, None before 'start' and after 'stop'
+///
+static WORKER: Mutex<Option<Worker>> = Mutex::new(None);
+
+/// A panic while logging must not disable the logs
+///
+fn lock() -> MutexGuard<'static, Option<Worker>> {
+    WORKER.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Start the writer thread if it is not running
+///
+/// 'writer' is only built when the thread must be started.
+///
+pub fn start<F: FnOnce() -> MultiWriter>(writer: F) {
+    let mut worker = lock();
+    if worker.is_some() {
+        return;
+    }
+    let mut writer = writer();
+    let (sender, receiver) = channel();
+    let thread = std::thread::Builder::new()
+        .name("pza-logs".to_string())
+        .spawn(move || {
+            while let Ok(WorkerMessage::Line(line)) = receiver.recv() {
+                let _ = writer.write_all(&line);
+            }
+            let _ = writer.flush();
+        });
+    match thread {
+        Ok(thread) => *worker = Some(Worker { sender, thread }),
+        Err(e) => eprintln!("Cannot start the log writer: {}", e),
+    }
+}
+
+/// Write the logs still waiting, then stop and join the writer thread
+///
+/// Logs emitted after are lost until the next 'start'.
+///
+pub fn stop() {
+    //
+    // Do not keep the lock while joining, the last logs need it
+    let worker = lock().take();
+    if let Some(worker) = worker {
+        let _ = worker.sender.send(WorkerMessage::Shutdown);
+        let _ = worker.thread.join();
+    }
+}
+
+/// Writer given to the subscriber, it sends each line to the writer thread
+///
+#[derive(Clone, Copy, Default)]
+pub struct WorkerWriter;
+
+impl Write for WorkerWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if let Some(worker) = lock().as_ref() {
+            let _ = worker.sender.send(WorkerMessage::Line(buf.to_vec()));
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for WorkerWriter {
+    type Writer = WorkerWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        *self
+    }
+}
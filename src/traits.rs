@@ -1,7 +1,8 @@
-use crate::{Instance, Error, ProductionOrder, Props};
+use crate::{Error, Instance, Props, ScanReporter, ScanResult};
 use async_trait::async_trait;
 use bytes::Bytes;
 use std::fmt::Debug;
+use std::time::Duration;
 
 /// Actions that are specific for each driver
 ///
@@ -18,6 +19,15 @@ pub trait DriverOperations: Send + Sync {
     /// Once this function return, the instance will reboot
     ///
     async fn wait_reboot_event(&mut self, mut instance: Instance);
+
+    ///
+    /// The instance is stopping, release the resources used by the driver
+    ///
+    /// The platform does not wait forever, this function must return quickly
+    ///
+    async fn unmount(&mut self, _instance: Instance) -> Result<(), Error> {
+        Ok(())
+    }
 }
 
 /// Trait to define a driver producer
@@ -47,18 +57,27 @@ pub trait Producer: Send {
     fn produce(&self) -> Result<Box<dyn DriverOperations>, Error>;
 }
 
+/// Search for instances that can be produced on this server
 ///
+/// Scanners run in parallel, slow blocking probes (serial port queries...)
+/// should be moved into 'tokio::task::spawn_blocking'.
 ///
-///
-pub trait Scanner: Send {
-    ///
+#[async_trait]
+pub trait Scanner: Send + Sync {
     ///
+    /// Name of the scanner, used in the logs and the scan notifications
     ///
     fn name(&self) -> String;
+
+    /// Time after which the scan is aborted and its results dropped
     ///
+    fn timeout(&self) -> Duration {
+        Duration::from_secs(10)
+    }
+
+    /// Scan the server, 'reporter' publishes the progress of the scan
     ///
-    ///
-    fn scan(&self) -> Vec<ProductionOrder>;
+    async fn scan(&self, reporter: ScanReporter) -> Vec<ScanResult>;
 }
 
 /// Trait to manage an message attribute (MQTT)
@@ -0,0 +1,414 @@
+use crate::{Error, Factory, InstanceSettings, ProductionOrder, ValidationReport};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Maximal depth of includes, protect against include loops
+///
+const MAX_INCLUDE_DEPTH: usize = 16;
+
+/// One instance declared in the device tree
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TreeEntry {
+    /// Name of the instance
+    ///
+    pub name: String,
+
+    /// Reference of the driver producer
+    ///
+    pub dref: String,
+
+    /// Settings given to the driver
+    ///
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub settings: Option<InstanceSettings>,
+
+    /// Disabled instances stay in the tree but are not produced
+    ///
+    #[serde(default = "default_enabled", skip_serializing_if = "is_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn is_enabled(enabled: &bool) -> bool {
+    *enabled
+}
+
+impl TreeEntry {
+    /// Production order of this entry
+    ///
+    pub fn production_order(&self) -> ProductionOrder {
+        ProductionOrder {
+            name: self.name.clone(),
+            dref: self.dref.clone(),
+            settings: self.settings.clone(),
+        }
+    }
+}
+
+impl From<ProductionOrder> for TreeEntry {
+    fn from(order: ProductionOrder) -> Self {
+        TreeEntry {
+            name: order.name,
+            dref: order.dref,
+            settings: order.settings,
+            enabled: true,
+        }
+    }
+}
+
+/// Content of a single tree file
+///
+#[derive(Default, Debug, Clone, Serialize, Deserialize)]
+struct TreeFile {
+    /// Other tree files to load, relative to this file
+    ///
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    includes: Vec<String>,
+
+    /// Instances declared in this file
+    ///
+    #[serde(default)]
+    devices: Vec<TreeEntry>,
+}
+
+/// Device tree of the platform (tree.json)
+///
+/// The file is parsed as JSON5, so comments and trailing commas are allowed.
+///
+/// ```json5
+/// {
+///     includes: ["benches/bench1.json"],
+///     devices: [
+///         // my power supply
+///         { name: "psu", dref: "vendor.psu", settings: { port: "/dev/ttyUSB0" } },
+///         { name: "scope", dref: "vendor.scope", enabled: false },
+///     ],
+/// }
+/// ```
+///
+#[derive(Default, Debug, Clone)]
+pub struct DeviceTree {
+    /// File from which the tree has been loaded
+    ///
+    path: Option<PathBuf>,
+
+    /// Content of the main file
+    ///
+    file: TreeFile,
+
+    /// Trees loaded from the includes
+    ///
+    included: Vec<DeviceTree>,
+}
+
+impl DeviceTree {
+    /// Load the tree from a file and its includes
+    ///
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<DeviceTree, Error> {
+        let tree = Self::load_file(path.as_ref(), 0)?;
+        tree.check_names()?;
+        Ok(tree)
+    }
+
+    /// Load the tree from the system default location
+    ///
+    pub fn load_system_default() -> Result<DeviceTree, Error> {
+        let path = crate::env::system_default_device_tree_file()
+            .map_err(|e| Error::TreeError(format!("{:?}", e)))?;
+        Self::load(path)
+    }
+
+    /// Parse a tree from a string, includes are relative to the current directory
+    ///
+    pub fn parse(text: &str) -> Result<DeviceTree, Error> {
+        let tree = Self::parse_file(text, None, 0)?;
+        tree.check_names()?;
+        Ok(tree)
+    }
+
+    fn load_file(path: &Path, depth: usize) -> Result<DeviceTree, Error> {
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| Error::TreeError(format!("{}: {}", path.display(), e)))?;
+        Self::parse_file(&text, Some(path), depth)
+    }
+
+    fn parse_file(text: &str, path: Option<&Path>, depth: usize) -> Result<DeviceTree, Error> {
+        let origin = path
+            .map(|p| p.display().to_string())
+            .unwrap_or("<string>".to_string());
+
+        if depth > MAX_INCLUDE_DEPTH {
+            return Err(Error::TreeError(format!(
+                "{}: too many nested includes (loop ?)",
+                origin
+            )));
+        }
+
+        let file: TreeFile = json5::from_str(text).map_err(|e| match e {
+            json5::Error::Message {
+                msg,
+                location: Some(location),
+            } => Error::TreeError(format!(
+                "{}:{}:{}: {}",
+                origin, location.line, location.column, msg
+            )),
+            json5::Error::Message {
+                msg,
+                location: None,
+            } => Error::TreeError(format!("{}: {}", origin, msg)),
+        })?;
+
+        let base_dir = path
+            .and_then(|p| p.parent())
+            .map(|p| p.to_path_buf())
+            .unwrap_or_default();
+        let mut included = Vec::new();
+        for include in &file.includes {
+            included.push(Self::load_file(&base_dir.join(include), depth + 1)?);
+        }
+
+        Ok(DeviceTree {
+            path: path.map(|p| p.to_path_buf()),
+            file,
+            included,
+        })
+    }
+
+    /// Instance names must be unique in the whole tree
+    ///
+    fn check_names(&self) -> Result<(), Error> {
+        let mut names = HashSet::new();
+        for entry in self.entries() {
+            if !names.insert(entry.name.as_str()) {
+                return Err(Error::TreeError(format!(
+                    "instance name '{}' is declared more than once",
+                    entry.name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Save the main file (includes are kept as references, comments are lost)
+    ///
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), Error> {
+        let text = serde_json::to_string_pretty(&self.file)
+            .map_err(|e| Error::SerializeFailure(format!("{:?}", e)))?;
+        std::fs::write(path.as_ref(), text)
+            .map_err(|e| Error::TreeError(format!("{}: {}", path.as_ref().display(), e)))
+    }
+
+    /// File from which the tree has been loaded
+    ///
+    pub fn path(&self) -> Option<&PathBuf> {
+        self.path.as_ref()
+    }
+
+    /// All the files of the tree (main file and includes)
+    ///
+    pub fn files(&self) -> Vec<PathBuf> {
+        let mut res: Vec<PathBuf> = self.path.iter().cloned().collect();
+        for tree in &self.included {
+            res.extend(tree.files());
+        }
+        res
+    }
+
+    /// All the entries of the tree, included ones first
+    ///
+    pub fn entries(&self) -> Vec<&TreeEntry> {
+        let mut res: Vec<&TreeEntry> = self.included.iter().flat_map(|t| t.entries()).collect();
+        res.extend(self.file.devices.iter());
+        res
+    }
+
+    /// Production orders of the enabled entries
+    ///
+    pub fn production_orders(&self) -> Vec<ProductionOrder> {
+        self.entries()
+            .into_iter()
+            .filter(|e| e.enabled)
+            .map(|e| e.production_order())
+            .collect()
+    }
+
+    /// Add an entry into the main file
+    ///
+    pub fn add_entry<E: Into<TreeEntry>>(&mut self, entry: E) -> Result<(), Error> {
+        let entry = entry.into();
+        if self.entries().iter().any(|e| e.name == entry.name) {
+            return Err(Error::TreeError(format!(
+                "instance name '{}' is already declared",
+                entry.name
+            )));
+        }
+        self.file.devices.push(entry);
+        Ok(())
+    }
+
+    /// Enable or disable an entry of the main file, false if the entry is not found
+    ///
+    pub fn set_enabled(&mut self, name: &str, enabled: bool) -> bool {
+        match self.file.devices.iter_mut().find(|e| e.name == name) {
+            Some(entry) => {
+                entry.enabled = enabled;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Check the enabled entries against the producers of the factory
+    ///
+    pub fn validate(&self, factory: &Factory) -> ValidationReport {
+        factory.validate_all(&mut self.production_orders())
+    }
+}
+
+/// Differences between two sets of production orders
+///
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct TreeDiff {
+    /// New instances to produce
+    ///
+    pub added: Vec<ProductionOrder>,
+
+    /// Names of the instances to stop
+    ///
+    pub removed: Vec<String>,
+
+    /// Instances with new settings, they must be remounted
+    ///
+    pub changed: Vec<ProductionOrder>,
+
+    /// Instances with a new driver reference, they must be stopped then produced again
+    ///
+    pub replaced: Vec<ProductionOrder>,
+}
+
+impl TreeDiff {
+    /// Compute what must be done to go from 'old' orders to 'new' orders
+    ///
+    pub fn between(old: &[ProductionOrder], new: &[ProductionOrder]) -> TreeDiff {
+        let mut diff = TreeDiff::default();
+        for order in new {
+            match old.iter().find(|o| o.name == order.name) {
+                None => diff.added.push(order.clone()),
+                Some(previous) if previous.dref != order.dref => diff.replaced.push(order.clone()),
+                Some(previous) if previous.settings != order.settings => {
+                    diff.changed.push(order.clone())
+                }
+                Some(_) => {}
+            }
+        }
+        for order in old {
+            if !new.iter().any(|o| o.name == order.name) {
+                diff.removed.push(order.name.clone());
+            }
+        }
+        diff
+    }
+
+    /// True if nothing changed
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.changed.is_empty()
+            && self.replaced.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DeviceTree, TreeDiff};
+    use crate::{Error, ProductionOrder};
+
+    #[test]
+    fn test_parse_tree() {
+        let tree = DeviceTree::parse(
+            r#"{
+                // bench power supply
+                devices: [
+                    { name: "psu", dref: "vendor.psu", settings: { port: "/dev/ttyUSB0" } },
+                    /* not plugged today */
+                    { name: "scope", dref: "vendor.scope", enabled: false },
+                ],
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(tree.entries().len(), 2);
+        let orders = tree.production_orders();
+        assert_eq!(orders.len(), 1);
+        assert_eq!(orders[0].name, "psu");
+
+        match DeviceTree::parse("{\n  devices: [\n    { name: \"psu\" dref: \"x\" }\n  ]\n}") {
+            Err(Error::TreeError(message)) => {
+                assert!(message.contains("<string>:3:"), "{}", message)
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_includes() {
+        let dir = std::env::temp_dir().join(format!("pza-tree-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(
+            dir.join("bench.json"),
+            r#"{ devices: [{ name: "psu", dref: "vendor.psu" }] }"#,
+        )
+        .unwrap();
+        std::fs::write(
+            dir.join("tree.json"),
+            r#"{ includes: ["bench.json"], devices: [{ name: "scope", dref: "vendor.scope" }] }"#,
+        )
+        .unwrap();
+
+        let tree = DeviceTree::load(dir.join("tree.json")).unwrap();
+        let names: Vec<String> = tree
+            .production_orders()
+            .into_iter()
+            .map(|o| o.name)
+            .collect();
+        assert_eq!(names, vec!["psu", "scope"]);
+
+        std::fs::write(
+            dir.join("tree.json"),
+            r#"{ includes: ["bench.json"], devices: [{ name: "psu", dref: "vendor.psu" }] }"#,
+        )
+        .unwrap();
+        assert!(DeviceTree::load(dir.join("tree.json")).is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_diff() {
+        let old = vec![
+            ProductionOrder::new("vendor.psu", "psu").add_u16_setting("channel", 1),
+            ProductionOrder::new("vendor.scope", "scope"),
+            ProductionOrder::new("vendor.dmm", "dmm"),
+        ];
+        let new = vec![
+            ProductionOrder::new("vendor.psu", "psu").add_u16_setting("channel", 2),
+            ProductionOrder::new("vendor.scope", "scope"),
+            ProductionOrder::new("other.dmm", "dmm"),
+            ProductionOrder::new("vendor.relay", "relay"),
+        ];
+        let diff = TreeDiff::between(&old, &new);
+        assert_eq!(diff.added, vec![new[3].clone()]);
+        assert_eq!(diff.changed, vec![new[0].clone()]);
+        assert_eq!(diff.replaced, vec![new[2].clone()]);
+        assert!(diff.removed.is_empty());
+
+        let diff = TreeDiff::between(&new, &new[1..]);
+        assert_eq!(diff.removed, vec!["psu".to_string()]);
+        assert!(TreeDiff::between(&new, &new).is_empty());
+    }
+}
@@ -0,0 +1,133 @@
+use async_trait::async_trait;
+use panduza_platform_core::instance::State;
+use panduza_platform_core::{
+    Container, DriverOperations, Error, Instance, LoopbackBroker, ProductionOrder, Reactor,
+    ReactorSettings,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Driver that mounts a 'channel' class with a 'voltage' attribute and a root 'control'
+///
+struct ChannelDriver {
+    mount_count: Arc<AtomicUsize>,
+}
+
+#[async_trait]
+impl DriverOperations for ChannelDriver {
+    async fn mount(&mut self, mut instance: Instance) -> Result<(), Error> {
+        let mut channel = instance.create_class("channel").finish().await;
+        channel
+            .create_attribute("voltage")
+            .with_ro()
+            .finish_as_si("V", 0.0, 30.0, 2)
+            .await?;
+        instance
+            .create_attribute("control")
+            .with_rw()
+            .finish_as_boolean()
+            .await?;
+        self.mount_count.fetch_add(1, Ordering::SeqCst);
+        Ok(())
+    }
+
+    async fn wait_reboot_event(&mut self, _instance: Instance) {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+/// Wait until the instance has been mounted 'count' times and is running
+///
+async fn wait_mounted(instance: &Instance, mount_count: &AtomicUsize, count: usize) {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while mount_count.load(Ordering::SeqCst) < count
+            || !matches!(instance.state().await, State::Running)
+        {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .expect("instance not mounted in time");
+}
+
+#[tokio::test]
+async fn test_container_lookups() {
+    let broker = LoopbackBroker::new();
+    let mount_count = Arc::new(AtomicUsize::new(0));
+    let driver = ChannelDriver {
+        mount_count: mount_count.clone(),
+    };
+    let mut instance = Reactor::new(ReactorSettings::default())
+        .start_loopback_instance(
+            &broker,
+            ProductionOrder::new("test.channel", "dev"),
+            Box::new(driver),
+        )
+        .await
+        .unwrap();
+
+    //
+    // '_' class of the instance, then the driver elements
+    let mut names = Vec::new();
+    for child in instance.children().await {
+        names.push(child.name().await);
+    }
+    assert_eq!(names, vec!["_", "channel", "control"]);
+
+    assert!(instance.find_class("channel").await.is_some());
+    assert!(instance.find_class("control").await.is_none());
+    assert!(instance.find_attribute("channel").await.is_none());
+    assert!(instance.find_element("channel/voltage/x").await.is_none());
+    let voltage = instance.find_attribute("channel/voltage").await.unwrap();
+    assert!(voltage.as_si().is_some());
+    assert!(voltage.as_boolean().is_none());
+    let control = instance.find_attribute("control").await.unwrap();
+    assert!(control.as_boolean().is_some());
+
+    //
+    // Disabling a class disables its sub tree
+    instance
+        .change_enablement_of("channel", false)
+        .await
+        .unwrap();
+    assert_eq!(
+        broker.retained("pza/dev/channel/voltage/enabled").unwrap(),
+        "false"
+    );
+    assert!(matches!(
+        instance.change_enablement_of("nothing", false).await,
+        Err(Error::InvalidArgument(_))
+    ));
+
+    //
+    // Elements created under a disabled class start disabled
+    let mut channel = instance.find_class("channel").await.unwrap();
+    channel
+        .create_attribute("current")
+        .with_ro()
+        .finish_as_si("A", 0.0, 5.0, 2)
+        .await
+        .unwrap();
+    let sub_class = channel.create_class("limits").finish().await;
+    assert!(!sub_class.is_enabled());
+    assert_eq!(
+        broker.retained("pza/dev/channel/current/enabled").unwrap(),
+        "false"
+    );
+    assert_eq!(
+        broker.retained("pza/dev/channel/limits/enabled").unwrap(),
+        "false"
+    );
+
+    //
+    // The driver elements of the previous mount are forgotten
+    instance.remount(None).await;
+    wait_mounted(&instance, &mount_count, 2).await;
+    assert_eq!(instance.children().await.len(), 3);
+    assert!(instance.find_attribute("channel/voltage").await.is_some());
+    assert_eq!(
+        broker.retained("pza/dev/channel/voltage/enabled").unwrap(),
+        "true"
+    );
+}
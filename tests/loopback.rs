@@ -0,0 +1,109 @@
+use async_trait::async_trait;
+use panduza_platform_core::{
+    Container, DriverOperations, Error, Instance, LoopbackBroker, LoopbackClient, ProductionOrder,
+    Reactor, ReactorSettings,
+};
+use std::time::Duration;
+
+#[tokio::test]
+async fn test_attribute_on_loopback() {
+    let broker = LoopbackBroker::new();
+    let mut reactor = Reactor::new(ReactorSettings::default());
+    reactor.start_loopback(&broker).unwrap();
+
+    let mut att = reactor
+        .create_new_attribute(None)
+        .with_topic("pza/dev/echo")
+        .with_rw()
+        .finish_as_boolean()
+        .await
+        .unwrap();
+    att.set(false).await.unwrap();
+    tokio::spawn(async move {
+        //
+        // Commands received before the first wait are already queued
+        loop {
+            while let Some(value) = att.pop_cmd().await {
+                att.set(value).await.unwrap();
+            }
+            att.wait_commands().await;
+        }
+    });
+
+    //
+    // The retained value comes first, then the echo of the command
+    let mut user = LoopbackClient::new(&broker);
+    user.watch_attribute("pza/dev/echo");
+    let timeout = Duration::from_secs(1);
+    assert_eq!(
+        user.next_value("pza/dev/echo", timeout).await.unwrap(),
+        "false"
+    );
+    user.send_command("pza/dev/echo", "true").await.unwrap();
+    assert_eq!(
+        user.next_value("pza/dev/echo", timeout).await.unwrap(),
+        "true"
+    );
+    assert_eq!(broker.retained("pza/dev/enabled"), None);
+    assert_eq!(broker.retained("pza/dev/echo/enabled").unwrap(), "true");
+}
+
+/// Driver whose 'echo' attribute publishes each command it receives
+///
+struct EchoDriver;
+
+#[async_trait]
+impl DriverOperations for EchoDriver {
+    async fn mount(&mut self, mut instance: Instance) -> Result<(), Error> {
+        let mut att = instance
+            .create_attribute("echo")
+            .with_rw()
+            .finish_as_boolean()
+            .await?;
+        att.set(false).await?;
+        instance
+            .spawn("echo", async move {
+                //
+                // Commands received before the first wait are already queued
+                loop {
+                    while let Some(value) = att.pop_cmd().await {
+                        att.set(value).await?;
+                    }
+                    att.wait_commands().await;
+                }
+            })
+            .await;
+        Ok(())
+    }
+
+    async fn wait_reboot_event(&mut self, _instance: Instance) {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+#[tokio::test]
+async fn test_driver_on_loopback() {
+    let broker = LoopbackBroker::new();
+    let instance = Reactor::new(ReactorSettings::default())
+        .start_loopback_instance(
+            &broker,
+            ProductionOrder::new("test.echo", "dev"),
+            Box::new(EchoDriver),
+        )
+        .await
+        .unwrap();
+    assert_eq!(instance.name(), "dev");
+
+    let mut user = LoopbackClient::new(&broker);
+    user.watch_attribute("pza/dev/echo");
+    let timeout = Duration::from_secs(1);
+    assert_eq!(
+        user.next_value("pza/dev/echo", timeout).await.unwrap(),
+        "false"
+    );
+    user.send_command("pza/dev/echo", "true").await.unwrap();
+    assert_eq!(
+        user.next_value("pza/dev/echo", timeout).await.unwrap(),
+        "true"
+    );
+}
@@ -0,0 +1,134 @@
+use panduza_platform_core::plugin::config::PluginConfig;
+use panduza_platform_core::{
+    Error, NotificationFilter, NotificationKind, PluginLoader, ProductionOrder,
+};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+/// Build the in-tree test plugin and copy it into an empty directory
+///
+/// The library path is taken from the cargo build messages, so it follows
+/// the target directory and the profile actually used.
+///
+fn prepare_plugin_dir(name: &str) -> PathBuf {
+    let output = Command::new(env!("CARGO"))
+        .args(["build", "-p", "pza-test-plugin", "--message-format=json"])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .stderr(Stdio::inherit())
+        .output()
+        .expect("cannot run cargo");
+    assert!(output.status.success(), "test plugin build failed");
+
+    let extension = panduza_platform_core::env::system_dyn_lib_extension().unwrap();
+    let library = String::from_utf8_lossy(&output.stdout)
+        .lines()
+        .filter_map(|line| serde_json::from_str::<serde_json::Value>(line).ok())
+        .filter(|message| {
+            message["reason"] == "compiler-artifact"
+                && message["target"]["name"] == "pza_test_plugin"
+        })
+        .flat_map(|message| message["filenames"].as_array().cloned().unwrap_or_default())
+        .filter_map(|file| file.as_str().map(PathBuf::from))
+        .find(|file| file.extension().is_some_and(|e| e == extension.as_str()))
+        .expect("test plugin library not found in cargo messages");
+
+    let plugin_dir =
+        std::env::temp_dir().join(format!("pza-plugins-{}-{}", name, std::process::id()));
+    std::fs::create_dir_all(&plugin_dir).unwrap();
+    std::fs::copy(&library, plugin_dir.join(library.file_name().unwrap())).unwrap();
+    plugin_dir
+}
+
+#[test]
+fn test_load_plugin() {
+    let plugin_dir = prepare_plugin_dir("load");
+
+    let config = PluginConfig::default()
+        .with_plugin_options("test", serde_json::json!({"instance_name": "configured"}));
+    let mut loader = PluginLoader::new(config);
+    let errors = loader.load_directories(std::slice::from_ref(&plugin_dir));
+    assert!(errors.is_empty(), "{:?}", errors);
+    assert_eq!(loader.plugins().len(), 1);
+    assert_eq!(loader.plugins()[0].handle().name().unwrap(), "test");
+    assert!(loader.store().contains(&"test.fake".to_string()));
+
+    let found = loader.scan();
+    assert_eq!(found.len(), 1);
+    assert_eq!(found[0].order.dref, "test.fake");
+    assert_eq!(found[0].order.name, "configured");
+
+    assert!(loader
+        .produce(&ProductionOrder::new("test.fake", "fake"))
+        .is_ok());
+    assert!(matches!(
+        loader.produce(&ProductionOrder::new("other.driver", "other")),
+        Err(Error::BadSettings(_))
+    ));
+
+    //
+    // Scan progress is reported once, later pulls only get new notifications
+    let scans =
+        loader.pull_notifications(&NotificationFilter::new().with_kind(NotificationKind::Scan));
+    assert!(!scans.is_empty());
+    assert!(loader
+        .pull_notifications(&NotificationFilter::new().with_kind(NotificationKind::Scan))
+        .is_empty());
+
+    //
+    // Notifications skipped by the filtered pull are still available without filter
+    let all = loader.pull_notifications(&NotificationFilter::new());
+    assert!(all.len() >= scans.len());
+    assert!(all.iter().any(|n| n.kind() == NotificationKind::Scan));
+
+    //
+    // Stop and unload, then start the same plugin again
+    let path = loader.plugins()[0].path().clone();
+    loader.unload(&path).unwrap();
+    assert!(loader.plugins().is_empty());
+    assert!(!loader.store().contains(&"test.fake".to_string()));
+    assert!(loader.unload(&path).is_err());
+
+    loader.load_file(&path).unwrap();
+    assert!(loader
+        .produce(&ProductionOrder::new("test.fake", "fake"))
+        .is_ok());
+    loader.unload(&path).unwrap();
+
+    std::fs::remove_dir_all(&plugin_dir).unwrap();
+}
+
+#[test]
+fn test_load_plugin_twice() {
+    let plugin_dir = prepare_plugin_dir("twice");
+    let mut loader = PluginLoader::new(PluginConfig::default());
+    let errors = loader.load_directories(std::slice::from_ref(&plugin_dir));
+    assert!(errors.is_empty(), "{:?}", errors);
+    let path = loader.plugins()[0].path().clone();
+
+    //
+    // The same file is refused without touching the running plugin
+    assert!(matches!(
+        loader.load_file(&path),
+        Err(Error::PluginError(_))
+    ));
+
+    //
+    // A copy is an other library, it is stopped and closed when its drivers are refused
+    let copy = plugin_dir.join(format!(
+        "copy-{}",
+        path.file_name().unwrap().to_string_lossy()
+    ));
+    std::fs::copy(&path, &copy).unwrap();
+    assert!(matches!(
+        loader.load_file(&copy),
+        Err(Error::PluginError(_))
+    ));
+
+    assert_eq!(loader.plugins().len(), 1);
+    assert!(loader
+        .produce(&ProductionOrder::new("test.fake", "fake"))
+        .is_ok());
+    loader.unload(&path).unwrap();
+
+    std::fs::remove_dir_all(&plugin_dir).unwrap();
+}
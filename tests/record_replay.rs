@@ -0,0 +1,118 @@
+use panduza_platform_core::{
+    read_recording, LoopbackBroker, LoopbackClient, MessageRecorder, MessageReplayer, Reactor,
+    ReactorSettings, RecordDirection, RecordEntry,
+};
+use std::path::PathBuf;
+use std::time::Duration;
+
+fn recording_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("pza-{}-{}.jsonl", name, std::process::id()))
+}
+
+/// Record a field session by hand: the attribute must echo each command
+///
+fn write_recording(path: &PathBuf, echoed: &[&str]) {
+    let recorder = MessageRecorder::create(path).unwrap();
+    for (command, value) in ["true", "false"].iter().zip(echoed) {
+        recorder
+            .record(&RecordEntry::new(
+                RecordDirection::Command,
+                "pza/dev/echo/cmd",
+                command.as_bytes(),
+            ))
+            .unwrap();
+        recorder
+            .record(&RecordEntry::new(
+                RecordDirection::Value,
+                "pza/dev/echo/att",
+                value.as_bytes(),
+            ))
+            .unwrap();
+    }
+    recorder.flush().unwrap();
+}
+
+/// Create an attribute that publishes each command it receives
+///
+async fn spawn_echo(reactor: &Reactor) {
+    let mut att = reactor
+        .create_new_attribute(None)
+        .with_topic("pza/dev/echo")
+        .with_rw()
+        .finish_as_boolean()
+        .await
+        .unwrap();
+    tokio::spawn(async move {
+        //
+        // Commands received before the first wait are already queued
+        loop {
+            while let Some(value) = att.pop_cmd().await {
+                att.set(value).await.unwrap();
+            }
+            att.wait_commands().await;
+        }
+    });
+}
+
+/// Mount an echo attribute on a reactor without broker, then replay
+///
+async fn replay(path: &PathBuf) -> panduza_platform_core::ReplayReport {
+    let replayer = MessageReplayer::from_file(path).unwrap();
+    let mut reactor = Reactor::new(ReactorSettings::default());
+    reactor
+        .start_with_client(replayer.message_client())
+        .unwrap();
+    spawn_echo(&reactor).await;
+
+    let report = replayer.replay(&reactor.message_dispatcher()).await;
+    std::fs::remove_file(path).ok();
+    report
+}
+
+#[tokio::test]
+async fn test_replay_matches_recording() {
+    let path = recording_path("replay-ok");
+    write_recording(&path, &["true", "false"]);
+    let report = replay(&path).await;
+    assert_eq!(report.commands, 2);
+    assert!(report.is_success(), "{:?}", report.mismatches);
+
+    let path = recording_path("replay-ko");
+    write_recording(&path, &["true", "true"]);
+    let report = replay(&path).await;
+    assert_eq!(report.mismatches.len(), 1);
+    assert_eq!(report.mismatches[0].actual, vec!["true", "false"]);
+}
+
+#[tokio::test]
+async fn test_record_then_replay() {
+    let path = recording_path("reactor");
+    let broker = LoopbackBroker::new();
+    let mut reactor =
+        Reactor::new(ReactorSettings::default().with_record_path(path.to_string_lossy()));
+    reactor.start_loopback(&broker).unwrap();
+    spawn_echo(&reactor).await;
+
+    let mut user = LoopbackClient::new(&broker);
+    user.watch_attribute("pza/dev/echo");
+    let timeout = Duration::from_secs(1);
+    for command in ["true", "false", "true"] {
+        user.send_command("pza/dev/echo", command).await.unwrap();
+        assert_eq!(
+            user.next_value("pza/dev/echo", timeout).await.unwrap(),
+            command
+        );
+    }
+    reactor.recorder().unwrap().flush().unwrap();
+
+    let entries = read_recording(&path).unwrap();
+    let directions: Vec<RecordDirection> = entries.iter().map(|e| e.direction).collect();
+    assert_eq!(
+        directions,
+        [RecordDirection::Command, RecordDirection::Value].repeat(3)
+    );
+
+    let report = replay(&path).await;
+    assert_eq!(report.commands, 3);
+    assert!(report.is_success(), "{:?}", report.mismatches);
+}
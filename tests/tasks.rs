@@ -0,0 +1,121 @@
+use async_trait::async_trait;
+use panduza_platform_core::instance::State;
+use panduza_platform_core::{
+    Container, DriverOperations, Error, Instance, LoopbackBroker, ProductionOrder, Reactor,
+    ReactorSettings, RestartPolicy,
+};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Tasks spawned by the driver during the mount
+///
+#[derive(Clone, Copy)]
+enum Tasks {
+    /// A plain task that ends with a timeout
+    TimeoutOnce,
+    /// A task that times out on its first run then keeps running, and a
+    /// task that completes each of its runs
+    FlakyAndSteady,
+}
+
+struct TaskDriver {
+    tasks: Tasks,
+}
+
+#[async_trait]
+impl DriverOperations for TaskDriver {
+    async fn mount(&mut self, mut instance: Instance) -> Result<(), Error> {
+        match self.tasks {
+            Tasks::TimeoutOnce => {
+                instance
+                    .spawn("timeout", async {
+                        tokio::time::sleep(Duration::from_millis(100)).await;
+                        Err(Error::Timeout("no answer".to_string()))
+                    })
+                    .await;
+            }
+            Tasks::FlakyAndSteady => {
+                let runs = Arc::new(AtomicUsize::new(0));
+                instance
+                    .spawn_with_policy("flaky", RestartPolicy::OnFailure, move || {
+                        let runs = runs.clone();
+                        async move {
+                            tokio::time::sleep(Duration::from_millis(100)).await;
+                            if runs.fetch_add(1, Ordering::SeqCst) == 0 {
+                                return Err(Error::Timeout("no answer".to_string()));
+                            }
+                            std::future::pending().await
+                        }
+                    })
+                    .await;
+                instance
+                    .spawn_with_policy("steady", RestartPolicy::Always, || async {
+                        tokio::time::sleep(Duration::from_millis(50)).await;
+                        Ok(())
+                    })
+                    .await;
+            }
+        }
+        Ok(())
+    }
+
+    async fn wait_reboot_event(&mut self, _instance: Instance) {
+        tokio::time::sleep(Duration::from_secs(1)).await;
+    }
+}
+
+async fn mount(broker: &LoopbackBroker, tasks: Tasks) -> Instance {
+    Reactor::new(ReactorSettings::default())
+        .start_loopback_instance(
+            broker,
+            ProductionOrder::new("test.tasks", "dev"),
+            Box::new(TaskDriver { tasks }),
+        )
+        .await
+        .unwrap()
+}
+
+/// Wait until the instance reaches 'expected'
+///
+async fn wait_state(instance: &Instance, expected: State) {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while instance.state().await != expected {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap_or_else(|_| panic!("instance not in state {}", expected));
+}
+
+#[tokio::test]
+async fn test_plain_task_recoverable_error() {
+    let broker = LoopbackBroker::new();
+    let instance = mount(&broker, Tasks::TimeoutOnce).await;
+    wait_state(&instance, State::Warning).await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert_eq!(instance.state().await, State::Warning);
+}
+
+#[tokio::test]
+async fn test_task_warnings_are_kept_per_task() {
+    let broker = LoopbackBroker::new();
+    let mut instance = mount(&broker, Tasks::FlakyAndSteady).await;
+    wait_state(&instance, State::Warning).await;
+
+    //
+    // The flaky task runs again and the steady one completes runs, the
+    // warning stays until it is cleared explicitly
+    tokio::time::sleep(Duration::from_secs(1)).await;
+    assert_eq!(instance.state().await, State::Warning);
+    {
+        let tasks = instance.tasks();
+        let tasks = tasks.lock().await;
+        let flaky = &tasks.entries()[0];
+        assert_eq!(flaky.restart_count, 1);
+        assert!(flaky.warning.is_some());
+    }
+
+    instance.clear_warning().await;
+    assert_eq!(instance.state().await, State::Running);
+}
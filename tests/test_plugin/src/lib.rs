@@ -0,0 +1,72 @@
+//! Minimal plugin used by the tests of the plugin loader
+//!
+
+use async_trait::async_trait;
+use panduza_platform_core::{
+    DriverOperations, Error, Instance, Producer, ProductionOrder, Props, ScanReporter, ScanResult,
+    Scanner,
+};
+
+panduza_platform_core::plugin_interface!("test");
+
+struct FakeDriver;
+
+#[async_trait]
+impl DriverOperations for FakeDriver {
+    async fn mount(&mut self, _instance: Instance) -> Result<(), Error> {
+        Ok(())
+    }
+
+    async fn wait_reboot_event(&mut self, _instance: Instance) {
+        tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    }
+}
+
+struct FakeProducer;
+
+impl Producer for FakeProducer {
+    fn manufacturer(&self) -> String {
+        "test".to_string()
+    }
+
+    fn model(&self) -> String {
+        "fake".to_string()
+    }
+
+    fn description(&self) -> String {
+        "Fake driver of the test plugin".to_string()
+    }
+
+    fn props(&self) -> Props {
+        Props::default()
+    }
+
+    fn produce(&self) -> Result<Box<dyn DriverOperations>, Error> {
+        Ok(Box::new(FakeDriver))
+    }
+}
+
+struct FakeScanner;
+
+#[async_trait]
+impl Scanner for FakeScanner {
+    fn name(&self) -> String {
+        "fake".to_string()
+    }
+
+    async fn scan(&self, _reporter: ScanReporter) -> Vec<ScanResult> {
+        //
+        // The host can rename the found instance with the plugin options
+        let options = plugin_options();
+        let name = options["instance_name"].as_str().unwrap_or("fake");
+        vec![ProductionOrder::new("test.fake", name).into()]
+    }
+}
+
+pub fn plugin_producers() -> Vec<Box<dyn Producer>> {
+    vec![Box::new(FakeProducer)]
+}
+
+pub fn plugin_scanners() -> Vec<Box<dyn Scanner>> {
+    vec![Box::new(FakeScanner)]
+}